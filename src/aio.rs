@@ -6,19 +6,40 @@ use cpal::{BufferSize, OutputCallbackInfo, SampleFormat, Stream, StreamConfig};
 use log::warn;
 use rtrb::{Consumer, Producer, RingBuffer};
 
-pub const BUFFER_SIZE: usize = 256;
+use hannover::BUFFER_SIZE;
 
 pub struct AudioIo {
     pub sample_rate: usize,
     pub audio_in: Producer<f32>,
+
+    /// The output stream. Audio plays for as long as this is kept alive.
+    #[allow(unused)]
     pub stream: Stream,
 }
 
-pub fn play_audio() -> anyhow::Result<AudioIo> {
+/// Get the names of all the available output devices.
+pub fn output_devices() -> anyhow::Result<Vec<String>> {
+    let host = cpal::default_host();
+    Ok(host
+        .output_devices()?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// Start playing audio on the output device with the given name, or the
+/// default one if `None`.
+pub fn play_audio(device: Option<&str>) -> anyhow::Result<AudioIo> {
     let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .ok_or_else(|| anyhow!("no output device"))?;
+    let device = match device {
+        Some(name) => host
+            .output_devices()?
+            .find(|device| device.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| anyhow!("no output device named '{name}'"))?,
+
+        None => host
+            .default_output_device()
+            .ok_or_else(|| anyhow!("no output device"))?,
+    };
 
     let mut supported_config = device.supported_output_configs()?;
     let supported_config = supported_config
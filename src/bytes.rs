@@ -32,6 +32,18 @@ impl<const N: usize> NibbleStream<N> {
         }
     }
 
+    /// Get the position of this stream in its data, and its running total.
+    pub fn position(&self) -> (usize, u8) {
+        (self.index, self.total)
+    }
+
+    /// Move this stream to a position previously returned by
+    /// [`NibbleStream::position`].
+    pub fn set_position(&mut self, index: usize, total: u8) {
+        self.index = if self.wrap == 0 { 0 } else { index % self.wrap };
+        self.total = total;
+    }
+
     /// Get the next `N` nibbles from this stream.
     pub fn next_nibbles(&mut self) -> [u8; N] {
         if self.data.is_empty() {
@@ -44,7 +56,7 @@ impl<const N: usize> NibbleStream<N> {
 
             // note: self.wrap is always even, so this should be correct
             let byte = self.data[(index % self.wrap) / 2] ^ self.total;
-            *data = if index % 2 == 0 {
+            *data = if index.is_multiple_of(2) {
                 self.total = self.total.rotate_left(5).wrapping_add(byte);
                 byte >> 4
             } else {
@@ -0,0 +1,433 @@
+//! Command line argument parsing.
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use hannover::notes::{Detuned, KeyboardMapping, Pitch, Scales, Tuning};
+use hannover::wav::{self, SampleFormat};
+use hannover::{Length, Program, Session, Settings, Timeline};
+
+/// The wavetable sizes the binary is compiled for.
+pub const TABLE_SIZES: [usize; 5] = [16, 32, 50, 64, 128];
+
+pub const USAGE: &str = "\
+usage: hannover [command] [options] [text...]
+
+commands:
+  live      play live from the terminal (default)
+  render    render a text to a WAV file
+  export    write the wavetable a text produces to a wavetable WAV file
+  inspect   print the notes a text produces
+  disasm    print the wavetable instructions a text produces
+  asm       print the nibble encoding of a wavetable program
+  devices   list the available output devices
+  help      print this message
+
+options:
+  --bpm <n>           tempo in quarter notes per minute (default 100)
+  --tempo-from-text   derive the tempo from the text instead
+  --time <signature>  time signature, like 3/4 or 6/8 (default 4/4)
+  --base <pitch>      base pitch as a note name like A2 or C#3, or a MIDI note
+                      number (default A2)
+  --scale <scale>     scale of every note, either a list of steps in semitones
+                      like 2,1,2,2,1,2,2 or one of major, dorian, phrygian,
+                      lydian, mixolydian, minor, locrian, harmonic-minor,
+                      melodic-minor, whole-tone, pentatonic-major,
+                      pentatonic-minor, blues or chromatic (default
+                      pentatonic-minor, with minor melodies)
+  --melody-scale <s>  scale of melodies and arpeggio chords only
+  --key-changes <m>   let the text change key every few bars, one of fifths
+                      (around the circle of fifths), relative (between the
+                      relative major and minor) or mixed
+  --key-every <bars>  bars between key changes (default 8)
+  --edo <n>           tune to n equal divisions of the octave, one per MIDI
+                      note number (default 12). Scales must then add up to
+                      n steps, and fifths are the nearest to 3/2
+  --scl <path>        tune to the scale in this Scala .scl file instead. Scales
+                      must then add up to its number of notes
+  --kbm <path>        map MIDI note numbers to the tuning's scale degrees as
+                      in this Scala .kbm keyboard mapping. Scales must then
+                      add up to its map size
+  --reference <pitch> frequency of A4 in Hz (default 440), or of another note
+                      as <pitch>=<hz> like C4=256, or a note tuned some cents
+                      away from A4 at 440 Hz like A4+25c
+  --voices <n>        number of simultaneous voices (default 8)
+  --generators <list> which note generator plays in each of the 16 states of
+                      the melody, as a comma separated list of raw, arp,
+                      melody or markov, repeated to fill all 16
+  --size <n>          wavetable size, one of 16, 32, 50, 64, 128 (default 50)
+  --attack <seconds>  note attack time (default 0.05)
+  --decay <seconds>   note decay time (default 0.4)
+  --aliased           play the wavetable back without band limiting
+  --interp-x <mode>   interpolation along the wave, one of nearest, linear,
+                      hermite or sinc (default linear)
+  --interp-y <mode>   interpolation along the morph axis (default linear)
+  --init <name>       how to fill the wavetable at the start, one of sine, saw,
+                      square, triangle, additive, fm or noise (default sine)
+  --init-from-text    derive the initializer from the text instead
+  --table <path>      start from the wavetable in this WAV file, either a single
+                      cycle or a series of frames, instead
+  --program <path>    run the wavetable program in this file instead of the
+                      instructions from the text
+  --load <path>       resume a saved session when playing, rendering or
+                      exporting, using its text if none is given. options
+                      after this one override the saved settings
+
+live options:
+  --device <name>     output device to play on
+  --save <path>       file to save the session to (default session.txt)
+  --record <path>     record every change of the text and every command to
+                      this file
+  --replay <path>     replay the events recorded in this file, ignoring what
+                      is typed
+
+  while playing, the up and down arrows change the tempo by 1 bpm, and page up
+  and page down by 10 bpm. F1-F4 save the wavetable to one of four slots and
+  F5-F8 recall it, F9 undoes the last quarter note of wavetable instructions,
+  F10 and F11 transpose down and up a semitone, and F12 freezes or unfreezes
+  the wavetable. ctrl-s saves the session.
+
+render options:
+  -o, --output <path>        file to write to (default out.wav)
+  -l, --length <bars|secs>   length as bars (8) or seconds (12.5s) (default 8,
+                             or until a bar after the last replayed event)
+  --rate <hz>                sample rate (default 48000)
+  --format <i16|i24|f32>     sample format (default i16)
+  --replay <path>            replay the events recorded in this file
+
+export options:
+  -o, --output <path>        file to write to (default out.wav)
+  -l, --length <bars|secs>   how long to run the text's program before
+                             exporting, as for render (default 8)
+  --format <i16|i24|f32>     sample format (default f32)
+  --frame-size <n>           samples per exported frame (default 2048)
+
+inspect and disasm options:
+  -n, --count <n>     number of notes or instructions to print (default 32)
+
+disasm options:
+  --json              print the instructions as JSON
+
+asm takes no text, but requires --program.
+";
+
+#[derive(Debug)]
+pub struct Cli {
+    pub settings: Settings,
+    pub size: usize,
+    pub command: Command,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Live {
+        device: Option<String>,
+        save: PathBuf,
+        record: Option<PathBuf>,
+        replay: Option<Timeline>,
+    },
+
+    Render {
+        text: String,
+        output: PathBuf,
+        length: Length,
+        sample_rate: usize,
+        format: SampleFormat,
+        replay: Option<Timeline>,
+    },
+
+    Export {
+        text: String,
+        output: PathBuf,
+        length: Length,
+        format: SampleFormat,
+        frame_size: usize,
+    },
+
+    Inspect {
+        text: String,
+        count: usize,
+    },
+
+    Disassemble {
+        text: String,
+        count: usize,
+        json: bool,
+    },
+
+    Assemble {
+        program: Program,
+    },
+
+    Devices,
+    Help,
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut args = args.into_iter().peekable();
+
+        let command = match args.peek().map(String::as_str) {
+            Some(
+                name @ ("live" | "render" | "export" | "inspect" | "disasm" | "asm" | "devices"
+                | "help"),
+            ) => {
+                let name = name.to_string();
+                args.next();
+                name
+            }
+
+            _ => String::from("live"),
+        };
+
+        let mut settings = Settings::default();
+        let mut size = 50;
+
+        let mut device = None;
+        let mut save = PathBuf::from("session.txt");
+        let mut record = None;
+        let mut replay = None;
+        let mut output = PathBuf::from("out.wav");
+        let mut length = None;
+        let mut sample_rate = 48_000;
+        let mut format = None;
+        let mut frame_size = 2048;
+        let mut count = 32;
+        let mut json = false;
+
+        let mut text = vec![];
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("missing value for {arg}"))
+            };
+
+            match arg.as_str() {
+                "--bpm" => settings.bpm = parse(&arg, value()?)?,
+                "--tempo-from-text" => settings.tempo_from_text = true,
+                "--time" => settings.time_signature = parse(&arg, value()?)?,
+                "--base" => settings.base = parse(&arg, value()?)?,
+                "--scale" => settings.scales = Scales::uniform(parse(&arg, value()?)?),
+                "--melody-scale" => settings.scales.melody = parse(&arg, value()?)?,
+                "--key-changes" => settings.key_changes = Some(parse(&arg, value()?)?),
+                "--key-every" => settings.key_every = parse(&arg, value()?)?,
+                "--edo" => {
+                    let mapping = settings.tuning.mapping.clone();
+                    settings.tuning = Tuning::equal(parse(&arg, value()?)?)?;
+                    settings.tuning.mapping = mapping;
+                }
+                "--scl" => {
+                    let mapping = settings.tuning.mapping.clone();
+                    settings.tuning = Tuning::read_scl(value()?.as_ref())?;
+                    settings.tuning.mapping = mapping;
+                }
+                "--kbm" => settings.tuning.mapping = KeyboardMapping::read_kbm(value()?.as_ref())?,
+                "--reference" => {
+                    let value = value()?;
+                    let (pitch, frequency) = match value.split_once('=') {
+                        Some((pitch, frequency)) => {
+                            (parse(&arg, pitch.into())?, parse(&arg, frequency.into())?)
+                        }
+                        None => match value.parse() {
+                            Ok(frequency) => (Pitch::from_midi(69), frequency),
+                            Err(_) => {
+                                let detuned: Detuned = parse(&arg, value)?;
+                                (detuned.pitch, detuned.as_frequency())
+                            }
+                        },
+                    };
+
+                    let mapping = &mut settings.tuning.mapping;
+                    mapping.reference = pitch.midi();
+                    mapping.frequency = frequency;
+                    if !(mapping.frequency.is_finite() && mapping.frequency > 0.0) {
+                        bail!("the reference frequency must be positive");
+                    }
+                }
+                "--voices" => settings.voices = parse(&arg, value()?)?,
+                "--generators" => {
+                    let generators = parse(&arg, value()?)?;
+                    settings.registry.check(&generators)?;
+                    settings.generators = generators;
+                }
+                "--size" => size = parse(&arg, value()?)?,
+                "--attack" => settings.attack = parse(&arg, value()?)?,
+                "--decay" => settings.decay = parse(&arg, value()?)?,
+                "--aliased" => settings.band_limited = false,
+                "--interp-x" => settings.interpolation_x = parse(&arg, value()?)?,
+                "--interp-y" => settings.interpolation_y = parse(&arg, value()?)?,
+                "--init" => settings.initializer = parse(&arg, value()?)?,
+                "--init-from-text" => settings.initializer_from_text = true,
+                "--table" => {
+                    let path = value()?;
+                    let file =
+                        File::open(&path).with_context(|| format!("could not open '{path}'"))?;
+                    let audio = wav::read(BufReader::new(file))
+                        .with_context(|| format!("could not read '{path}'"))?;
+                    if audio.samples.is_empty() {
+                        bail!("'{path}' contains no samples");
+                    }
+                    settings.table = Some(audio);
+                }
+                "--program" => {
+                    let path = value()?;
+                    let source = fs::read_to_string(&path)
+                        .with_context(|| format!("could not read '{path}'"))?;
+                    let program = source.parse().with_context(|| format!("in '{path}'"))?;
+                    settings.program = Some(program);
+                }
+
+                "--load" => {
+                    let path = value()?;
+                    let session = Session::read(path.as_ref())?;
+                    settings
+                        .load(&session)
+                        .with_context(|| format!("in '{path}'"))?;
+                    size = session
+                        .get("size")
+                        .with_context(|| format!("in '{path}'"))?;
+                    settings.session = Some(session);
+                }
+
+                "--device" => device = Some(value()?),
+                "--save" => save = PathBuf::from(value()?),
+                "--record" => record = Some(PathBuf::from(value()?)),
+                "--replay" => replay = Some(Timeline::read(value()?.as_ref())?),
+
+                "-o" | "--output" => output = PathBuf::from(value()?),
+                "-l" | "--length" => length = Some(parse(&arg, value()?)?),
+                "--rate" => sample_rate = parse(&arg, value()?)?,
+                "--format" => format = Some(parse(&arg, value()?)?),
+                "--frame-size" => frame_size = parse(&arg, value()?)?,
+
+                "-n" | "--count" => count = parse(&arg, value()?)?,
+                "--json" => json = true,
+
+                "-h" | "--help" => {
+                    return Ok(Self {
+                        settings,
+                        size,
+                        command: Command::Help,
+                    })
+                }
+
+                "--" => text.extend(args.by_ref()),
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    bail!("unknown option '{flag}'")
+                }
+
+                _ => text.push(arg),
+            }
+        }
+
+        if !TABLE_SIZES.contains(&size) {
+            bail!("unsupported wavetable size {size} (expected one of {TABLE_SIZES:?})");
+        }
+
+        if frame_size == 0 {
+            bail!("the frame size must be positive");
+        }
+
+        if settings.bpm == 0 {
+            bail!("the tempo must be positive");
+        }
+
+        if !(settings.attack.is_finite() && settings.attack > 0.0) {
+            bail!("the attack time must be positive");
+        }
+
+        if !(settings.decay.is_finite() && settings.decay >= 0.0) {
+            bail!("the decay time must not be negative");
+        }
+
+        if settings.voices == 0 {
+            bail!("there must be at least one voice");
+        }
+
+        if settings.key_every == 0 {
+            bail!("key changes must be at least one bar apart");
+        }
+
+        if sample_rate == 0 {
+            bail!("the sample rate must be positive");
+        }
+
+        let period = settings.tuning.period();
+        for scale in [&settings.scales.notes, &settings.scales.melody] {
+            if scale.period() as usize != period {
+                bail!(
+                    "scale '{scale}' repeats every {} steps, but the tuning every {period} \
+                     (give --scale and --melody-scale steps adding up to {period})",
+                    scale.period()
+                );
+            }
+        }
+
+        let text = match &settings.session {
+            Some(session) if text.is_empty() => session.text()?,
+            _ => text.join(" "),
+        };
+
+        let command = match command.as_str() {
+            "live" => Command::Live {
+                device,
+                save,
+                record,
+                replay,
+            },
+            "render" => Command::Render {
+                text,
+                output,
+                length: length.unwrap_or_else(|| match &replay {
+                    Some(timeline) => {
+                        let bar = settings.time_signature.bar().deltas();
+                        let bars = timeline.end().div_ceil(bar);
+                        Length::Bars(bars + 1)
+                    }
+                    None => Length::Bars(8),
+                }),
+                sample_rate,
+                format: format.unwrap_or(SampleFormat::Int16),
+                replay,
+            },
+            "export" => Command::Export {
+                text,
+                output,
+                length: length.unwrap_or(Length::Bars(8)),
+                format: format.unwrap_or(SampleFormat::Float32),
+                frame_size,
+            },
+            "inspect" => Command::Inspect { text, count },
+            "disasm" => Command::Disassemble { text, count, json },
+            "asm" => match settings.program.clone() {
+                Some(program) => Command::Assemble { program },
+                None => bail!("asm requires --program"),
+            },
+            "devices" => Command::Devices,
+            "help" => Command::Help,
+            _ => unreachable!(),
+        };
+
+        Ok(Self {
+            settings,
+            size,
+            command,
+        })
+    }
+}
+
+fn parse<T>(flag: &str, value: String) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    value
+        .parse()
+        .map_err(Into::into)
+        .with_context(|| format!("invalid value '{value}' for {flag}"))
+}
@@ -0,0 +1,150 @@
+//! The musical time of a performance.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+
+use crate::notes::Duration;
+
+/// How many beats of which note value make up a bar.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TimeSignature {
+    beats: usize,
+    unit: usize,
+}
+
+impl TimeSignature {
+    pub const COMMON: TimeSignature = TimeSignature { beats: 4, unit: 4 };
+
+    /// Create a time signature of `beats` beats per bar, each lasting a
+    /// `1/unit` note. The unit must be a power of two from a whole note down
+    /// to a thirty-second note.
+    pub fn new(beats: usize, unit: usize) -> anyhow::Result<Self> {
+        if beats == 0 {
+            bail!("a bar must have at least one beat");
+        }
+
+        if !unit.is_power_of_two() || unit > 32 {
+            bail!("the beat must be a whole note, or a half, quarter, 8th, 16th or 32nd");
+        }
+
+        Ok(Self { beats, unit })
+    }
+
+    pub fn beats(&self) -> usize {
+        self.beats
+    }
+
+    /// Get the length of a beat.
+    pub fn beat(&self) -> Duration {
+        Duration::from_ticks(Duration::WHOLE.ticks() / self.unit)
+    }
+
+    /// Get the length of a bar.
+    pub fn bar(&self) -> Duration {
+        self.beats * self.beat()
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self::COMMON
+    }
+}
+
+impl Display for TimeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.beats, self.unit)
+    }
+}
+
+impl FromStr for TimeSignature {
+    type Err = anyhow::Error;
+
+    /// Parse a time signature like `4/4` or `6/8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid time signature '{s}' (expected one like 3/4)");
+        let (beats, unit) = s.split_once('/').ok_or_else(invalid)?;
+        Self::new(
+            beats.parse().map_err(|_| invalid())?,
+            unit.parse().map_err(|_| invalid())?,
+        )
+    }
+}
+
+/// A position in a performance, counted in ticks from its start and read as
+/// bars and beats.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Clock {
+    now: Duration,
+    signature: TimeSignature,
+}
+
+impl Clock {
+    pub fn new(signature: TimeSignature) -> Self {
+        Self {
+            now: Duration::ZERO,
+            signature,
+        }
+    }
+
+    /// Get the time since the start of the performance.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn set_now(&mut self, now: Duration) {
+        self.now = now;
+    }
+
+    pub fn signature(&self) -> TimeSignature {
+        self.signature
+    }
+
+    /// Move forwards by `by`, and get the number of bars started on the way,
+    /// including one starting right where it ends.
+    pub fn advance(&mut self, by: Duration) -> usize {
+        let before = self.bar();
+        self.now += by;
+        self.bar() - before
+    }
+
+    /// Get the current bar, counting from zero.
+    pub fn bar(&self) -> usize {
+        self.now.ticks() / self.signature.bar().ticks()
+    }
+
+    /// Get the current beat within the bar, counting from zero.
+    pub fn beat(&self) -> usize {
+        (self.now.ticks() % self.signature.bar().ticks()) / self.signature.beat().ticks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, TimeSignature};
+    use crate::notes::Duration;
+
+    #[test]
+    fn bars_and_beats() {
+        let waltz: TimeSignature = "3/4".parse().unwrap();
+        assert_eq!("3/4", waltz.to_string());
+        assert_eq!(Duration::QUARTER * 3, waltz.bar());
+        assert_eq!(
+            Duration::EIGHT * 6,
+            "6/8".parse::<TimeSignature>().unwrap().bar()
+        );
+        assert!("4/3".parse::<TimeSignature>().is_err());
+        assert!("0/4".parse::<TimeSignature>().is_err());
+
+        let mut clock = Clock::new(waltz);
+        assert_eq!(0, clock.advance(Duration::ZERO));
+        assert_eq!(0, clock.advance(Duration::QUARTER * 2));
+        assert_eq!((0, 2), (clock.bar(), clock.beat()));
+        assert_eq!(1, clock.advance(Duration::QUARTER));
+        assert_eq!((1, 0), (clock.bar(), clock.beat()));
+        assert_eq!(1, clock.advance(Duration::QUARTER * 4));
+        assert_eq!((2, 1), (clock.bar(), clock.beat()));
+    }
+}
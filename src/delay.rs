@@ -1,5 +1,18 @@
 use crate::structures::FixedQueue;
 
+/// The longest delay time, in samples: two minutes at 192 kHz.
+pub const MAX_SAMPLES: usize = 2 * 60 * 192_000;
+
+/// The parameters of a [`Delay`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DelaySettings {
+    /// The delay time, in samples.
+    pub samples: usize,
+    pub feedback: f64,
+    pub dry: f64,
+    pub wet: f64,
+}
+
 pub struct Delay {
     mem: FixedQueue<f64>,
     feedback: f64,
@@ -17,6 +30,15 @@ impl Delay {
         }
     }
 
+    pub fn from_settings(settings: DelaySettings) -> Self {
+        Self::new(
+            settings.samples,
+            settings.feedback,
+            settings.dry,
+            settings.wet,
+        )
+    }
+
     pub fn process(&mut self, sample: f64) -> f64 {
         let echo = *self.mem.get();
         let feedback = sample + self.feedback * self.wet * echo;
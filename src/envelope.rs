@@ -1,3 +1,7 @@
+/// The number of seconds a released envelope takes to fall silent. Short, so
+/// notes end on time, but long enough not to click.
+const RELEASE: f64 = 0.005;
+
 #[derive(Clone, Copy, Debug)]
 pub struct AttackDecay {
     /// Number of seconds for the attack.
@@ -9,6 +13,9 @@ pub struct AttackDecay {
 
     /// A monotonically increasing time value.
     value: f64,
+
+    /// The time value and level this envelope was released at, if it was.
+    released: Option<(f64, f64)>,
 }
 
 impl AttackDecay {
@@ -18,11 +25,21 @@ impl AttackDecay {
             attack,
             decay_cumulative,
             value: 0.0,
+            released: None,
         }
     }
 
     pub fn reset(&mut self) {
         self.value = 0.0;
+        self.released = None;
+    }
+
+    /// Fade out from the current level within a few milliseconds, instead of
+    /// decaying completely.
+    pub fn release(&mut self) {
+        if self.released.is_none() {
+            self.released = Some((self.value, self.value()));
+        }
     }
 
     pub fn step(&mut self, by: f64) {
@@ -30,7 +47,10 @@ impl AttackDecay {
     }
 
     pub fn is_done(&self) -> bool {
-        self.value >= self.decay_cumulative
+        match self.released {
+            Some((at, _)) if self.value >= at + RELEASE => true,
+            _ => self.value >= self.decay_cumulative,
+        }
     }
 
     pub fn value(&self) -> f64 {
@@ -38,9 +58,12 @@ impl AttackDecay {
             value,
             attack,
             decay_cumulative,
+            released,
         } = self;
 
-        if value >= decay_cumulative {
+        if let Some((at, level)) = released {
+            (level * (1.0 - (value - at) / RELEASE)).max(0.0)
+        } else if value >= decay_cumulative {
             0.0
         } else if value > attack {
             1.0 - (value - attack) / (decay_cumulative - attack)
@@ -0,0 +1,383 @@
+//! Pluggable ways of coming up with the next note.
+//!
+//! Every 7 notes, a [`NoteSource`] picks one of 16 states from the text, and a
+//! [`GeneratorMap`] says which [`NoteGenerator`] plays in each state. The
+//! generators themselves are created by name from a [`Registry`], which has
+//! the built in ones and can be extended with new ones.
+//!
+//! [`NoteSource`]: crate::source::NoteSource
+
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::bail;
+
+use crate::bytes::NibbleStream;
+use crate::markov::MarkovMelody;
+use crate::melody::Melody;
+use crate::notes::{Duration, Note, Pitch, Scales};
+use crate::sequence::{Direction, Sequence};
+use crate::session::Session;
+
+/// The number of states a [`GeneratorMap`] covers.
+pub const STATES: usize = 16;
+
+/// Something which comes up with notes.
+///
+/// There is one instance of every generator a [`GeneratorMap`] names, shared
+/// by all the states it is mapped to. It is asked for a note only in those
+/// states, but gets to observe every note played.
+pub trait NoteGenerator: Debug + Send {
+    /// Get the note following `prev`. Returning `None` plays a note straight
+    /// from the text instead, as if it came from [`Context::raw_note`].
+    fn next_note(&mut self, prev: Note, ctx: &mut Context) -> Option<Note>;
+
+    /// Observe a note that was just played. `asked` tells whether this
+    /// generator was the one asked for it, even if it declined.
+    fn observe(&mut self, _note: Note, _asked: bool, _ctx: &mut Context) {}
+
+    /// Follow a change of the input text.
+    fn update_input(&mut self, _input: &[u8]) {}
+
+    /// Save the state of this generator. Keys must not clash with those of
+    /// other generators.
+    fn save(&self, _session: &mut Session) {}
+
+    fn load(&mut self, _session: &Session) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// The state of a [`NoteSource`] shared by all of its generators.
+///
+/// [`NoteSource`]: crate::source::NoteSource
+pub struct Context<'a> {
+    /// The pitch every generated melody is relative to.
+    pub base: Pitch,
+
+    /// The scales generated melodies draw their pitches from.
+    pub scales: &'a Scales,
+
+    /// The current state, between 0 and 15.
+    pub state: u8,
+
+    /// The number of notes played before this one.
+    pub count: usize,
+
+    pub(crate) notes: &'a mut NibbleStream<3>,
+    pub(crate) random: &'a mut NibbleStream<2>,
+}
+
+impl Context<'_> {
+    /// Get a note straight from the text.
+    pub fn raw_note(&mut self) -> Note {
+        self.notes.next_note(self.base, &self.scales.notes)
+    }
+
+    /// Get a random byte from the text.
+    pub fn random_byte(&mut self) -> u8 {
+        let [a, b] = self.random.next_nibbles();
+        (a << 4) | b
+    }
+}
+
+/// A generator along with the name it was registered under.
+pub type Named = (String, Box<dyn NoteGenerator>);
+
+type Constructor = dyn Fn(&[u8]) -> Box<dyn NoteGenerator> + Send + Sync;
+
+/// A list of named generators a [`GeneratorMap`] can refer to.
+#[derive(Clone)]
+pub struct Registry {
+    entries: Vec<(String, Arc<Constructor>)>,
+}
+
+impl Registry {
+    /// Create a registry with no generators.
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Create a registry with the built in generators:
+    ///
+    /// - `raw`: notes straight from the text.
+    /// - `arp`: arpeggios of the chord on every 5th note.
+    /// - `melody`: small steps along the melody scale.
+    /// - `markov`: a Markov chain of every note played so far.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register("raw", |_| Box::new(Raw));
+        registry.register("arp", |_| Box::new(Arpeggio::new()));
+        registry.register("melody", |input| Box::new(Melody::new(input)));
+        registry.register("markov", |_| Box::new(MarkovMelody::new()));
+        registry
+    }
+
+    /// Add a generator created by `f` from the input text, under the given
+    /// name. Replaces any generator already registered under that name.
+    pub fn register<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&[u8]) -> Box<dyn NoteGenerator> + Send + Sync + 'static,
+    {
+        let f: Arc<Constructor> = Arc::new(f);
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = f,
+            None => self.entries.push((name.into(), f)),
+        }
+    }
+
+    /// Get the names of all registered generators, in the order they were
+    /// registered in.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().any(|n| n == name)
+    }
+
+    /// Make sure every generator `map` names is registered.
+    pub fn check(&self, map: &GeneratorMap) -> anyhow::Result<()> {
+        if let Some(name) = map.names().iter().find(|name| !self.contains(name)) {
+            let known: Vec<_> = self.names().collect();
+            bail!(
+                "unknown generator '{name}' (expected one of {})",
+                known.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Create the generators `map` names for the given input text, in
+    /// registration order and along with their names, and get the index of
+    /// the generator for each state.
+    ///
+    /// Panics if `map` names an unregistered generator, see
+    /// [`Registry::check`].
+    pub fn create(&self, map: &GeneratorMap, input: &[u8]) -> (Vec<Named>, [usize; STATES]) {
+        let used: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(name, _)| map.names().contains(name))
+            .collect();
+
+        let states = map.names().each_ref().map(|name| {
+            used.iter()
+                .position(|(n, _)| n == name)
+                .unwrap_or_else(|| panic!("unknown generator '{name}'"))
+        });
+
+        let generators = used
+            .iter()
+            .map(|(name, f)| (name.clone(), f(input)))
+            .collect();
+        (generators, states)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Which generator, by name, plays in each of the 16 states.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneratorMap {
+    names: [String; STATES],
+}
+
+impl GeneratorMap {
+    pub fn new(names: [String; STATES]) -> Self {
+        Self { names }
+    }
+
+    pub fn names(&self) -> &[String; STATES] {
+        &self.names
+    }
+
+    /// Get the name of the generator for the given state.
+    pub fn get(&self, state: u8) -> &str {
+        &self.names[state as usize % STATES]
+    }
+
+    pub fn set(&mut self, state: u8, name: &str) {
+        self.names[state as usize % STATES] = name.into();
+    }
+}
+
+impl Default for GeneratorMap {
+    /// The original mix: raw notes in 5 states, arpeggios in 4, melodies in 5
+    /// and the Markov chain in 2.
+    fn default() -> Self {
+        const DEFAULT: [&str; STATES] = [
+            "raw", "arp", "melody", "raw", "markov", "arp", "melody", "raw", "melody", "arp",
+            "raw", "melody", "arp", "markov", "raw", "melody",
+        ];
+
+        Self::new(DEFAULT.map(String::from))
+    }
+}
+
+impl Display for GeneratorMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.names.join(","))
+    }
+}
+
+impl FromStr for GeneratorMap {
+    type Err = anyhow::Error;
+
+    /// Parse a comma separated list of generator names, which is repeated
+    /// until it covers all 16 states.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names: Vec<_> = s.split(',').map(str::trim).collect();
+        if names.iter().any(|name| name.is_empty()) {
+            bail!("empty generator name in '{s}'");
+        }
+
+        if names.len() > STATES {
+            bail!(
+                "expected at most {STATES} generators, found {}",
+                names.len()
+            );
+        }
+
+        Ok(Self::new(std::array::from_fn(|state| {
+            names[state % names.len()].to_string()
+        })))
+    }
+}
+
+/// Notes straight from the text.
+#[derive(Debug)]
+struct Raw;
+
+impl NoteGenerator for Raw {
+    fn next_note(&mut self, _prev: Note, ctx: &mut Context) -> Option<Note> {
+        Some(ctx.raw_note())
+    }
+}
+
+/// Arpeggios of the chord around every 5th note played.
+#[derive(Debug)]
+struct Arpeggio {
+    sequence: Sequence,
+}
+
+impl Arpeggio {
+    fn new() -> Self {
+        Self {
+            sequence: Sequence::new(vec![], Direction::Up),
+        }
+    }
+}
+
+impl NoteGenerator for Arpeggio {
+    fn next_note(&mut self, _prev: Note, _ctx: &mut Context) -> Option<Note> {
+        self.sequence.next_note()
+    }
+
+    fn observe(&mut self, note: Note, _asked: bool, ctx: &mut Context) {
+        if !ctx.count.is_multiple_of(5) {
+            return;
+        }
+
+        if let Some(pitch) = note.pitch {
+            // the last states go up in triplets, one chord per beat
+            let (dir, duration) = match ctx.state >> 2 {
+                0b00 => (Direction::Up, Duration::EIGHT),
+                0b01 => (Direction::Down, Duration::EIGHT),
+                0b10 => (Direction::PingPong, Duration::EIGHT),
+                _ => (Direction::Up, Duration::EIGHT.tuplet(3, 2)),
+            };
+
+            if let Some(arp) = Sequence::new_arp(ctx.base, ctx.scales, pitch, dir, duration) {
+                self.sequence = arp;
+            }
+        }
+    }
+
+    fn save(&self, session: &mut Session) {
+        self.sequence.save(session, "arp");
+    }
+
+    fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        self.sequence = Sequence::load(session, "arp")?;
+        Ok(())
+    }
+}
+
+impl NoteGenerator for Melody {
+    fn next_note(&mut self, prev: Note, ctx: &mut Context) -> Option<Note> {
+        Some(self.next(ctx.base, &ctx.scales.melody, prev))
+    }
+
+    fn update_input(&mut self, input: &[u8]) {
+        Melody::update_input(self, input);
+    }
+
+    fn save(&self, session: &mut Session) {
+        Melody::save(self, session);
+    }
+
+    fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        Melody::load(self, session)
+    }
+}
+
+impl NoteGenerator for MarkovMelody {
+    fn next_note(&mut self, prev: Note, ctx: &mut Context) -> Option<Note> {
+        let random = ctx.random_byte();
+        self.next(prev, random)
+    }
+
+    /// Learn from every note, except those this chain was asked for, which it
+    /// has already learned from by then.
+    fn observe(&mut self, note: Note, asked: bool, ctx: &mut Context) {
+        if !asked {
+            let random = ctx.random_byte();
+            let _ = self.next(note, random);
+        }
+    }
+
+    fn save(&self, session: &mut Session) {
+        MarkovMelody::save(self, session);
+    }
+
+    fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        MarkovMelody::load(self, session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeneratorMap, Registry};
+
+    #[test]
+    fn parse_and_check() {
+        let map: GeneratorMap = "raw, markov".parse().unwrap();
+        assert_eq!("raw", map.get(0));
+        assert_eq!("markov", map.get(15));
+        assert_eq!(Ok(map.clone()), map.to_string().parse().map_err(|_| ()));
+
+        let registry = Registry::builtin();
+        registry.check(&map).unwrap();
+        registry.check(&GeneratorMap::default()).unwrap();
+        assert!(registry.check(&"raw,drums".parse().unwrap()).is_err());
+
+        let (generators, states) = registry.create(&map, b"hello");
+        assert_eq!(2, generators.len());
+        assert_eq!([0, 1], [states[0], states[1]]);
+
+        assert!("raw,,arp".parse::<GeneratorMap>().is_err());
+    }
+}
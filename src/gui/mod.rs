@@ -1,8 +1,10 @@
 mod poll;
 
-pub use poll::{InputPoller, WavetablePoller};
+pub use poll::{InputPoller, StatusPoller};
 
 use std::io::{stdout, Stdout, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
 use std::time::Duration;
 
 use bresenham::Bresenham;
@@ -14,8 +16,14 @@ use crossterm::{cursor, QueueableCommand};
 use itertools::Itertools;
 use single_value_channel::Updater;
 
+use hannover::{Command, SNAPSHOT_SLOTS};
+
 const WT_VIZ_WIDTH: usize = 48;
 const WT_VIZ_HEIGHT: usize = 8;
+const STATUS_HEIGHT: usize = 2;
+/// The number of wavetable instructions a single undo reverts, a quarter note's
+/// worth.
+const UNDO_STEPS: usize = 8;
 const WT_LETTERS: [char; 16] = [
     ' ', '.', '.', '_', '\'', '|', '/', 'j', '\'', '\\', '|', 'L', '^', '\\', '/', '#',
 ];
@@ -49,27 +57,59 @@ pub struct Gui {
     cursor: usize,
     max_cursor: usize,
     send: Updater<String>,
-    recv: WavetablePoller,
+    recv: StatusPoller,
+    commands: Sender<Command>,
+
+    bpm: usize,
+    frozen: bool,
+    key: String,
+    position: String,
+    message: String,
+    note: String,
+    save_path: PathBuf,
 
     wt: [[char; WT_VIZ_WIDTH]; WT_VIZ_HEIGHT],
 }
 
 impl Gui {
-    pub fn run(send: Updater<String>, recv: WavetablePoller) -> Result<(), GuiError> {
+    pub fn run(
+        send: Updater<String>,
+        recv: StatusPoller,
+        commands: Sender<Command>,
+        bpm: usize,
+        text: String,
+        save_path: PathBuf,
+    ) -> Result<(), GuiError> {
         terminal::enable_raw_mode()?;
-        let result = Self::event_loop(send, recv);
+        let result = Self::event_loop(send, recv, commands, bpm, text, save_path);
         terminal::disable_raw_mode()?;
 
         result
     }
 
-    fn event_loop(send: Updater<String>, recv: WavetablePoller) -> Result<(), GuiError> {
+    fn event_loop(
+        send: Updater<String>,
+        recv: StatusPoller,
+        commands: Sender<Command>,
+        bpm: usize,
+        text: String,
+        save_path: PathBuf,
+    ) -> Result<(), GuiError> {
         let mut this = Self {
-            text: String::new(),
-            cursor: 0,
-            max_cursor: 0,
+            cursor: text.len(),
+            max_cursor: text.len(),
+            text,
             send,
             recv,
+            commands,
+
+            bpm,
+            frozen: false,
+            key: String::new(),
+            position: String::new(),
+            message: String::new(),
+            note: String::new(),
+            save_path,
 
             wt: [[' '; WT_VIZ_WIDTH]; WT_VIZ_HEIGHT],
         };
@@ -120,8 +160,7 @@ impl Gui {
                     if let Some((index, _)) = self
                         .text
                         .char_indices()
-                        .filter(|(index, _)| *index < self.cursor)
-                        .last()
+                        .rfind(|(index, _)| *index < self.cursor)
                     {
                         self.text.remove(index);
                         self.cursor = index;
@@ -171,6 +210,11 @@ impl Gui {
                         c.to_uppercase().collect()
                     } else if modifiers.contains(KeyModifiers::CONTROL) && c == 'c' {
                         return Err(GuiError::Interrupted);
+                    } else if modifiers.contains(KeyModifiers::CONTROL) && c == 's' {
+                        let _ = self
+                            .commands
+                            .send(Command::SaveSession(self.save_path.clone()));
+                        return Ok(false);
                     } else {
                         String::from(c)
                     };
@@ -179,11 +223,37 @@ impl Gui {
                     self.cursor += c.len();
                 }
 
+                KeyCode::Up => self.set_tempo(self.bpm.saturating_add(1)),
+                KeyCode::Down => self.set_tempo(self.bpm.saturating_sub(1)),
+                KeyCode::PageUp => self.set_tempo(self.bpm.saturating_add(10)),
+                KeyCode::PageDown => self.set_tempo(self.bpm.saturating_sub(10)),
+
                 KeyCode::Esc => {}
+                // F1-F4 save a snapshot, and F5-F8 recall it
+                KeyCode::F(n @ 1..=8) => {
+                    let slot = (n as usize - 1) % SNAPSHOT_SLOTS;
+                    let _ = self.commands.send(if (n as usize) <= SNAPSHOT_SLOTS {
+                        Command::SaveSnapshot(slot)
+                    } else {
+                        Command::RecallSnapshot(slot)
+                    });
+                }
+                KeyCode::F(9) => {
+                    let _ = self.commands.send(Command::Undo(UNDO_STEPS));
+                }
+                KeyCode::F(10) => {
+                    let _ = self.commands.send(Command::Transpose(-1));
+                }
+                KeyCode::F(11) => {
+                    let _ = self.commands.send(Command::Transpose(1));
+                }
+                KeyCode::F(12) => {
+                    self.frozen = !self.frozen;
+                    let _ = self.commands.send(Command::Freeze(self.frozen));
+                }
                 KeyCode::F(_) => {}
                 KeyCode::BackTab | KeyCode::Insert => {}
                 KeyCode::CapsLock | KeyCode::ScrollLock | KeyCode::NumLock => {}
-                KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {}
                 KeyCode::Null | KeyCode::PrintScreen | KeyCode::Pause | KeyCode::Menu => {}
                 KeyCode::KeypadBegin | KeyCode::Media(_) | KeyCode::Modifier(_) => {}
             },
@@ -198,19 +268,27 @@ impl Gui {
         Ok(false)
     }
 
+    /// Send a tempo change to the player.
+    fn set_tempo(&mut self, bpm: usize) {
+        self.bpm = bpm.max(1);
+        let _ = self.commands.send(Command::SetTempo(self.bpm));
+    }
+
     fn unrender(&self, stdout: &mut Stdout) -> Result<(), GuiError> {
         let clear: String = (0..self.max_cursor).map(|_| ' ').collect();
         stdout.queue(style::Print(clear))?;
 
         let clear: String = (0..WT_VIZ_WIDTH).map(|_| ' ').collect();
-        for _ in 0..WT_VIZ_HEIGHT {
+        for _ in 0..WT_VIZ_HEIGHT + STATUS_HEIGHT {
             stdout
                 .queue(cursor::MoveToNextLine(1))?
                 .queue(style::Print(&clear))?;
         }
 
         stdout
-            .queue(cursor::MoveToPreviousLine(WT_VIZ_HEIGHT as u16))?
+            .queue(cursor::MoveToPreviousLine(
+                (WT_VIZ_HEIGHT + STATUS_HEIGHT) as u16,
+            ))?
             .flush()?;
 
         Ok(())
@@ -237,6 +315,14 @@ impl Gui {
                 .queue(cursor::MoveToNextLine(1))?;
         }
 
+        // draw status
+        newlines += 2;
+        stdout
+            .queue(style::Print(format!("{:<WT_VIZ_WIDTH$}", self.status())))?
+            .queue(cursor::MoveToNextLine(1))?
+            .queue(style::Print(format!("{:<WT_VIZ_WIDTH$}", self.note)))?
+            .queue(cursor::MoveToNextLine(1))?;
+
         // reset cursor
         let column = 2 + self
             .text
@@ -255,10 +341,42 @@ impl Gui {
         Ok(())
     }
 
+    fn status(&self) -> String {
+        let mut status = format!("bpm {}  bar {}", self.bpm, self.position);
+        if !self.key.is_empty() {
+            status.push_str("  key ");
+            status.push_str(&self.key);
+        }
+
+        if self.frozen {
+            status.push_str("  frozen");
+        }
+
+        if !self.message.is_empty() {
+            status.push_str("  ");
+            status.push_str(&self.message);
+        }
+
+        status
+    }
+
     fn update(&mut self) -> Result<(), GuiError> {
-        if let Some(wt) = self.recv.poll() {
+        if let Some(status) = self.recv.poll() {
+            self.bpm = status.bpm;
+            self.frozen = status.frozen;
+            self.key = status.key.to_string();
+            self.position = format!("{}.{}", status.bar + 1, status.beat + 1);
+            self.message = status.message.clone();
+            self.note = match &status.note {
+                Some((note, provenance)) => {
+                    let pitch = note.pitch.map_or("rest".into(), |pitch| pitch.to_string());
+                    format!("note {pitch}:{}  {provenance}", note.duration)
+                }
+                None => String::new(),
+            };
+
             // create a "high-res" image, and downsample to appropriate letters.
-            let wt = draw_wavetable(wt);
+            let wt = draw_wavetable(&status.wavetable);
             for (y, row) in self.wt.iter_mut().enumerate() {
                 for (x, v) in row.iter_mut().enumerate() {
                     let a = wt[2 * y][2 * x];
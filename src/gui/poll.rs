@@ -1,5 +1,7 @@
 use single_value_channel::Receiver;
 
+use hannover::Status;
+
 #[derive(Debug)]
 pub struct InputPoller {
     prev: String,
@@ -27,19 +29,22 @@ impl InputPoller {
     }
 }
 
-pub struct WavetablePoller {
-    prev: Vec<u8>,
-    recv: Receiver<Vec<u8>>,
+pub struct StatusPoller {
+    prev: Status,
+    recv: Receiver<Status>,
 }
 
-impl WavetablePoller {
-    pub fn new(recv: Receiver<Vec<u8>>) -> Self {
-        Self { prev: vec![], recv }
+impl StatusPoller {
+    pub fn new(recv: Receiver<Status>) -> Self {
+        Self {
+            prev: Status::default(),
+            recv,
+        }
     }
 
-    /// Poll the audio processor for the current wave. Returns `None` if it
+    /// Poll the audio processor for its current status. Returns `None` if it
     /// hasn't changed since last poll.
-    pub fn poll(&mut self) -> Option<&[u8]> {
+    pub fn poll(&mut self) -> Option<&Status> {
         let curr = self.recv.latest();
         if curr != &self.prev {
             self.prev = curr.clone();
@@ -0,0 +1,70 @@
+//! HANNOVER turns text into procedural music.
+//!
+//! The text is read as streams of nibbles by a [`NoteSource`], which picks the
+//! melody, and by a [`Performer`], which plays it on a [`Wavetable`] that the
+//! same text keeps reshaping. A [`Player`] adds the effects on top and renders
+//! samples, deterministically, from a list of timestamped events.
+//!
+//! ```no_run
+//! use hannover::{Player, Settings};
+//!
+//! let mut player = Player::<32>::new(b"hello", 48_000, &Settings::default());
+//! let mut samples = vec![0.0; 48_000];
+//! player.render(&mut samples);
+//! ```
+//!
+//! Everything the crate supports is re-exported here, at the root, along with
+//! the [`notes`] types and the [`wav`] reader. The modules behind them are
+//! private, so how voices, the sampler or the wavetable mipmaps work can
+//! change without breaking callers:
+//!
+//! - playback: [`Player`], driven by [`Command`]s and [`Event`]s, reporting a
+//!   [`Status`], and [`render_to_file`] or [`export_to_file`] for offline use;
+//! - the music: [`NoteSource`], its [`NoteGenerator`]s in a [`Registry`], and
+//!   the [`Performer`] with its [`Clock`];
+//! - the sound: [`Wavetable`], its [`Instruction`]s, [`Program`]s written in
+//!   the table assembly, and [`trace`] to inspect what the text does to it;
+//! - the effects: [`Delay`] and the [`AttackDecay`] envelope, configured by
+//!   [`Settings`];
+//! - saving and replaying: [`Session`], [`Timeline`] and [`Recorder`].
+
+mod bytes;
+mod clock;
+mod data;
+mod delay;
+mod envelope;
+mod float;
+mod generator;
+mod markov;
+mod math;
+mod melody;
+pub mod notes;
+mod performer;
+mod player;
+mod render;
+mod sampler;
+mod sequence;
+mod session;
+mod settings;
+mod source;
+mod structures;
+mod timeline;
+mod voice;
+pub mod wav;
+mod wavetable;
+
+pub use bytes::NibbleStream;
+pub use clock::{Clock, TimeSignature};
+pub use delay::{Delay, DelaySettings};
+pub use envelope::AttackDecay;
+pub use generator::{Context, GeneratorMap, NoteGenerator, Registry};
+pub use notes::{Duration, Note, Pitch};
+pub use performer::{initial_table, Performer};
+pub use player::{Command, Event, Player, Status, BUFFER_SIZE, SNAPSHOT_SLOTS};
+pub use render::{export_to_file, render, render_to_file, Length};
+pub use session::Session;
+pub use settings::Settings;
+pub use source::{NoteSource, Origin, Provenance};
+pub use timeline::{Change, Recorder, Timeline};
+pub use wavetable::asm::Program;
+pub use wavetable::{trace, Band, Initializer, Instruction, Interpolation, Snapshot, Wavetable};
@@ -0,0 +1,141 @@
+//! Live performance: the real-time wrapper around [`Player`], feeding it the
+//! text and commands from the interface and its samples to the audio device.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use single_value_channel::{channel_starting_with, Updater};
+
+use crate::aio::{self, AudioIo};
+use crate::gui::{self, InputPoller, StatusPoller};
+use hannover::{Command, Event, Player, Recorder, Settings, Status, Timeline, BUFFER_SIZE};
+
+/// Play the performance live, with the interface in the foreground. If a
+/// timeline is given, its events are replayed and what is typed is ignored.
+pub fn live<const S: usize>(
+    settings: Settings,
+    device: Option<String>,
+    save: PathBuf,
+    record: Option<PathBuf>,
+    replay: Option<Timeline>,
+) -> anyhow::Result<()> {
+    let text = match &settings.session {
+        Some(session) => session.text()?,
+        None => String::new(),
+    };
+
+    let (recv, send) = channel_starting_with(text.clone());
+    let (status_recv, status_send) = channel_starting_with(Status::default());
+    let (command_send, command_recv) = mpsc::channel();
+
+    let recorder = record.as_deref().map(Recorder::create).transpose()?;
+    let poll = InputPoller::new(recv);
+    let status_poll = StatusPoller::new(status_recv);
+
+    let bpm = settings.bpm;
+    let input_thread =
+        thread::spawn(move || gui::Gui::run(send, status_poll, command_send, bpm, text, save));
+
+    let player_thread = thread::spawn(move || {
+        let aio = aio::play_audio(device.as_deref())?;
+        play::<S>(
+            aio,
+            &settings,
+            poll,
+            command_recv,
+            status_send,
+            recorder,
+            replay,
+        )
+    });
+
+    let result = input_thread.join();
+
+    // the interface stops by itself when the player does, so report why
+    if player_thread.is_finished() {
+        match player_thread.join() {
+            Ok(result) => result?,
+            Err(e) => std::panic::resume_unwind(e),
+        }
+    }
+
+    match result {
+        Ok(Ok(()) | Err(gui::GuiError::Interrupted)) => Ok(()),
+        Ok(Err(e)) => Err(e.into()),
+        Err(e) => std::panic::resume_unwind(e),
+    }
+}
+
+/// Drive a [`Player`] in real time. Input and commands are scheduled for the
+/// next [`Duration::DELTA`] boundary as they arrive, and recorded along with
+/// the tick they took effect at.
+///
+/// [`Duration::DELTA`]: hannover::Duration::DELTA
+fn play<const S: usize>(
+    mut aio: AudioIo,
+    settings: &Settings,
+    mut input: InputPoller,
+    commands: Receiver<Command>,
+    status: Updater<Status>,
+    mut recorder: Option<Recorder>,
+    replay: Option<Timeline>,
+) -> anyhow::Result<()> {
+    let typed = input.poll().unwrap_or("").to_string();
+    let (data, events) = match &replay {
+        Some(timeline) => {
+            let (start, events) = timeline.split_start();
+            (start.unwrap_or(&typed), events)
+        }
+        None => (typed.as_str(), &[][..]),
+    };
+
+    if let Some(recorder) = &mut recorder {
+        recorder.record(0, &Event::Input(data.into()))?;
+    }
+
+    let mut player = Player::<S>::new(data.as_bytes(), aio.sample_rate, settings);
+    if let Some(session) = &settings.session {
+        player.load(session)?;
+    }
+
+    for change in events {
+        player.schedule(change.tick, change.event.clone());
+    }
+
+    status.update(player.status()).unwrap();
+
+    let mut buffer = [0.0; BUFFER_SIZE];
+    loop {
+        player.render(&mut buffer);
+
+        let chunk = loop {
+            if let Ok(chunk) = aio.audio_in.write_chunk_uninit(buffer.len()) {
+                break chunk;
+            }
+        };
+
+        chunk.fill_from_iter(buffer.iter().copied());
+
+        let mut events = vec![];
+        if let Some(data) = input.poll() {
+            if replay.is_none() {
+                events.push(Event::Input(data.into()));
+            }
+        }
+
+        events.extend(commands.try_iter().map(Event::Command));
+
+        for event in events {
+            let tick = player.schedule_next(event.clone());
+
+            if let Some(recorder) = &mut recorder {
+                if let Err(e) = recorder.record(tick, &event) {
+                    player.report(format!("recording failed: {e}"));
+                }
+            }
+        }
+
+        status.update(player.status()).unwrap();
+    }
+}
@@ -1,46 +1,146 @@
 mod aio;
-mod bytes;
-mod data;
-mod delay;
-mod envelope;
-mod float;
+mod cli;
 mod gui;
-mod markov;
-mod math;
-mod melody;
-mod notes;
-mod performer;
-mod player;
-mod sampler;
-mod sequence;
-mod source;
-mod structures;
-mod voice;
-mod wavetable;
-
-use std::thread;
-
-use single_value_channel::channel_starting_with;
-
-fn main() {
+mod live;
+
+use hannover::{
+    export_to_file, initial_table, render_to_file, trace, NibbleStream, NoteSource, Settings,
+};
+
+use crate::cli::{Cli, Command};
+
+/// Call the function `f` with the wavetable `size` as its const parameter. The
+/// sizes must match [`cli::TABLE_SIZES`].
+macro_rules! with_size {
+    ($size:expr, $($f:ident)::+($($arg:expr),* $(,)?)) => {
+        match $size {
+            16 => $($f)::+::<16>($($arg),*),
+            32 => $($f)::+::<32>($($arg),*),
+            50 => $($f)::+::<50>($($arg),*),
+            64 => $($f)::+::<64>($($arg),*),
+            128 => $($f)::+::<128>($($arg),*),
+            size => unreachable!("unsupported wavetable size {size}"),
+        }
+    };
+}
+
+fn main() -> anyhow::Result<()> {
     pretty_env_logger::init();
 
-    let (recv, send) = channel_starting_with(String::new());
-    let (wt_recv, wt_send) = channel_starting_with(vec![]);
+    let Cli {
+        settings,
+        size,
+        command,
+    } = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            eprintln!("run 'hannover help' for usage");
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Live {
+            device,
+            save,
+            record,
+            replay,
+        } => with_size!(size, live::live(settings, device, save, record, replay)),
+
+        Command::Render {
+            text,
+            output,
+            length,
+            sample_rate,
+            format,
+            replay,
+        } => with_size!(
+            size,
+            render_to_file(
+                &text,
+                length,
+                sample_rate,
+                &settings,
+                format,
+                &output,
+                replay
+            )
+        ),
+
+        Command::Export {
+            text,
+            output,
+            length,
+            format,
+            frame_size,
+        } => with_size!(
+            size,
+            export_to_file(&text, length, &settings, frame_size, format, &output)
+        ),
+
+        Command::Inspect { text, count } => {
+            inspect(&text, count, &settings);
+            Ok(())
+        }
+
+        Command::Disassemble { text, count, json } => {
+            with_size!(size, disassemble(&text, &settings, count, json));
+            Ok(())
+        }
 
-    let poll = gui::InputPoller::new(recv);
-    let wt_poll = gui::WavetablePoller::new(wt_recv);
+        Command::Assemble { program } => {
+            let nibbles = program.nibbles().iter().map(|n| format!("{n:x}"));
+            println!("{}", nibbles.collect::<String>());
+            Ok(())
+        }
 
-    let input_thread = thread::spawn(|| gui::Gui::run(send, wt_poll));
+        Command::Devices => {
+            for name in aio::output_devices()? {
+                println!("{name}");
+            }
+
+            Ok(())
+        }
+
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(())
+        }
+    }
+}
+
+/// Print the first `count` notes `text` produces.
+fn inspect(text: &str, count: usize, settings: &Settings) {
+    let mut source =
+        NoteSource::with_generators(text.as_bytes(), &settings.registry, &settings.generators);
+
+    for _ in 0..count {
+        let (note, provenance) = source.next(settings.base, &settings.scales);
+        match note.pitch {
+            Some(pitch) => print!("{pitch:>4} {:>4}  {:>5}", pitch.midi(), note.duration),
+            None => print!("rest       {:>5}", note.duration),
+        }
+
+        println!("  {provenance}");
+    }
+}
 
-    let _player_thread = thread::spawn(|| {
-        let aio = aio::play_audio().unwrap();
-        player::play(aio.audio_in, aio.sample_rate, poll, wt_send);
-    });
+/// Print the first `count` wavetable instructions `text` produces, along with
+/// the cursors after each.
+fn disassemble<const S: usize>(text: &str, settings: &Settings, count: usize, json: bool) {
+    let table = initial_table::<S>(text.as_bytes(), settings);
+    let steps = match settings.program.clone() {
+        Some(mut program) => trace::trace(table, || program.next_nibble(), count),
+        None => {
+            let mut nibbles = NibbleStream::<1>::new(text.as_bytes());
+            trace::trace(table, || nibbles.next_nibble(), count)
+        }
+    };
 
-    match input_thread.join() {
-        Ok(Ok(()) | Err(gui::GuiError::Interrupted)) => {}
-        Ok(Err(e)) => Err(e).unwrap(),
-        Err(e) => Err(e).unwrap(),
+    if json {
+        print!("{}", trace::json(&steps));
+    } else {
+        print!("{}", trace::listing(&steps));
     }
 }
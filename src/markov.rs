@@ -1,23 +1,24 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
-use std::hash::Hash;
 
+use anyhow::Context;
 use ordered_float::OrderedFloat;
 
 use crate::notes::{Duration, Note, Pitch};
+use crate::session::{self, Session};
 
 #[derive(Debug, Default)]
 pub struct MarkovMelody {
-    pitches: HashMap<Option<Pitch>, HashMap<Option<Pitch>, usize>>,
-    durations: HashMap<Duration, HashMap<Duration, usize>>,
+    pitches: BTreeMap<Option<Pitch>, BTreeMap<Option<Pitch>, usize>>,
+    durations: BTreeMap<Duration, BTreeMap<Duration, usize>>,
     prev: Option<Note>,
 }
 
 impl MarkovMelody {
     pub fn new() -> Self {
         Self {
-            pitches: HashMap::new(),
-            durations: HashMap::new(),
+            pitches: BTreeMap::new(),
+            durations: BTreeMap::new(),
             prev: None,
         }
     }
@@ -64,6 +65,83 @@ impl MarkovMelody {
     }
 }
 
+impl MarkovMelody {
+    pub fn save(&self, session: &mut Session) {
+        let pitch = |pitch: &Option<Pitch>| match pitch {
+            Some(pitch) => pitch.midi().to_string(),
+            None => String::from("-"),
+        };
+
+        for (from, choices) in &self.pitches {
+            for (to, count) in choices {
+                let (from, to) = (pitch(from), pitch(to));
+                session.set("markov.pitch", format!("{from} {to} {count}"));
+            }
+        }
+
+        for (from, choices) in &self.durations {
+            for (to, count) in choices {
+                session.set("markov.duration", format!("{from} {to} {count}"));
+            }
+        }
+
+        if let Some(prev) = self.prev {
+            session.set("markov.prev", prev);
+        }
+    }
+
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let mut loaded = Self::new();
+        for value in session.get_all("markov.pitch") {
+            let (from, to, count) = pitch_entry(value).context("in 'markov.pitch'")?;
+            *loaded
+                .pitches
+                .entry(from)
+                .or_default()
+                .entry(to)
+                .or_default() = count;
+        }
+
+        for value in session.get_all("markov.duration") {
+            let [from, to, count] =
+                session::fields::<String, 3>(value).context("in 'markov.duration'")?;
+            let (from, to): (Duration, Duration) = (
+                from.parse().context("in 'markov.duration'")?,
+                to.parse().context("in 'markov.duration'")?,
+            );
+
+            *loaded
+                .durations
+                .entry(from)
+                .or_default()
+                .entry(to)
+                .or_default() = count.parse().context("in 'markov.duration'")?;
+        }
+
+        loaded.prev = if session.has("markov.prev") {
+            Some(session.get("markov.prev")?)
+        } else {
+            None
+        };
+
+        *self = loaded;
+        Ok(())
+    }
+}
+
+/// Parse a saved pitch transition, where rests are written as `-`.
+fn pitch_entry(value: &str) -> anyhow::Result<(Option<Pitch>, Option<Pitch>, usize)> {
+    let pitch = |value: &str| -> anyhow::Result<Option<Pitch>> {
+        Ok(match value {
+            "-" => None,
+            midi => Some(midi.parse()?),
+        })
+    };
+
+    let [from, to, count] = session::fields::<String, 3>(value)?;
+    Ok((pitch(&from)?, pitch(&to)?, count.parse()?))
+}
+
 /// Build a 16-element array of `T`s from `choices`, where each `choice` appears
 /// approximately proportionally to their proportion of the sum of the values in
 /// `choices`. This is a "fair chance" algorithm, because even if a key occupies
@@ -71,7 +149,7 @@ impl MarkovMelody {
 /// way, the most frequent choices won't dominate the array.
 ///
 /// Panics if `choices` is empty.
-fn fair_chance_array<T: Copy + Debug + Eq + Hash>(choices: &HashMap<T, usize>) -> [T; 16] {
+fn fair_chance_array<T: Copy + Debug + Ord>(choices: &BTreeMap<T, usize>) -> [T; 16] {
     assert!(!choices.is_empty());
 
     let factor = 16.0 / choices.values().copied().sum::<usize>() as f64;
@@ -85,7 +163,7 @@ fn fair_chance_array<T: Copy + Debug + Eq + Hash>(choices: &HashMap<T, usize>) -
 
     let mut res = Vec::with_capacity(16);
     let mut index = 0;
-    let mut counts: HashMap<T, usize> = HashMap::new();
+    let mut counts: BTreeMap<T, usize> = BTreeMap::new();
 
     for (item, _) in sorted.iter() {
         counts.insert(*item, 0);
@@ -3,9 +3,11 @@ pub trait MathExt {
     fn max_abs(self, other: Self) -> Self;
 
     /// Compute the greatest common divisor of two numbers.
+    #[allow(dead_code)]
     fn gcd(self, other: Self) -> Self;
 
     /// Compute the least common multiple of two numbers.
+    #[allow(dead_code)]
     fn lcm(self, other: Self) -> Self;
 }
 
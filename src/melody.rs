@@ -1,5 +1,6 @@
 use crate::bytes::NibbleStream;
-use crate::notes::{Duration, Note, Pitch};
+use crate::notes::{Duration, Note, Pitch, Scale};
+use crate::session::Session;
 
 #[derive(Debug)]
 pub struct Melody {
@@ -19,11 +20,32 @@ impl Melody {
         self.nibbles = self.nibbles.with_new_data(input);
     }
 
-    pub fn next(&mut self, base: Pitch, current: Note) -> Note {
+    pub fn save(&self, session: &mut Session) {
+        session.set_stream("melody.nibbles", &self.nibbles);
+        if let Some(interval) = self.prev_interval {
+            session.set("melody.interval", interval);
+        }
+    }
+
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let mut nibbles = self.nibbles.clone();
+        session.get_stream("melody.nibbles", &mut nibbles)?;
+        let prev_interval = if session.has("melody.interval") {
+            Some(session.get("melody.interval")?)
+        } else {
+            None
+        };
+
+        self.nibbles = nibbles;
+        self.prev_interval = prev_interval;
+        Ok(())
+    }
+
+    /// Get the note following `current`, moving along `scale` starting at
+    /// `base`.
+    pub fn next(&mut self, base: Pitch, scale: &Scale, current: Note) -> Note {
         let nib = self.nibbles.next_nibble();
-        let current_num = current
-            .pitch
-            .and_then(|pitch| base.minor_scale_number(pitch));
+        let current_num = current.pitch.and_then(|pitch| scale.degree(base, pitch));
 
         let (pitch, duration) = if nib & 8 == 0 {
             let mut diff = match nib & 3 {
@@ -39,7 +61,7 @@ impl Melody {
 
             let pitch = current_num
                 .map(|num| num as i32 + diff)
-                .map(|num| (num, base.in_minor(num)));
+                .map(|num| (num, scale.pitch(base, num)));
 
             (pitch, current.duration)
         } else {
@@ -50,9 +72,9 @@ impl Melody {
 
                 0b11 => current
                     .pitch
-                    .and_then(|pitch| base.minor_scale_number(pitch))
+                    .and_then(|pitch| scale.degree(base, pitch))
                     .and_then(|num| self.prev_interval.map(|interval| num as i32 - interval))
-                    .map(|num| (num, base.in_minor(num))),
+                    .map(|num| (num, scale.pitch(base, num))),
 
                 _ => unreachable!(),
             };
@@ -62,7 +84,7 @@ impl Melody {
                 0b00 => duration,
                 0b01 => duration.dotted(),
                 0b10 => 2 * duration,
-                0b11 => (2 * duration).dotted(),
+                0b11 => duration.tuplet(3, 2),
 
                 _ => unreachable!(),
             };
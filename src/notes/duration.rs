@@ -1,39 +1,108 @@
-use std::ops::{Add, AddAssign, Mul, MulAssign};
+use std::fmt::Display;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub};
+use std::str::FromStr;
 
-/// A note duration. Implemented as a multiple of thirty-second notes.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+use anyhow::{anyhow, bail};
+
+/// A note duration. Implemented as a number of ticks, of which there are
+/// [`Duration::PPQN`] in a quarter note, so that tuplets divide evenly.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Duration(usize);
 
 impl Duration {
-    pub const EIGHT: Duration = Duration(4);
-    pub const SIXTEENTH: Duration = Duration(2);
+    /// The number of ticks per quarter note.
+    pub const PPQN: usize = 960;
+
+    pub const WHOLE: Duration = Duration(4 * Self::PPQN);
+    pub const QUARTER: Duration = Duration(Self::PPQN);
+    pub const EIGHT: Duration = Duration(Self::PPQN / 2);
+    pub const SIXTEENTH: Duration = Duration(Self::PPQN / 4);
+
+    /// A thirty-second note, the step a performance advances by.
+    pub const DELTA: Duration = Duration(Self::PPQN / 8);
 
     /// The smallest non-zero duration represented by this.
-    pub const DELTA: Duration = Duration(1);
+    pub const TICK: Duration = Duration(1);
 
     pub const ZERO: Duration = Duration(0);
 
+    /// Get the duration of the given number of ticks.
+    pub const fn from_ticks(ticks: usize) -> Self {
+        Self(ticks)
+    }
+
     /// Get the number of seconds this duration lasts, for the given BPM. The
     /// BPM measures the number of *beats* - quarter notes - in one minute.
     pub fn as_time(&self, bpm: usize) -> f64 {
         let beats_per_second = bpm as f64 / 60.0;
-        let num_beats = self.0 as f64 / 8.0;
+        let num_beats = self.0 as f64 / Self::PPQN as f64;
 
         num_beats / beats_per_second
     }
 
+    pub fn ticks(&self) -> usize {
+        self.0
+    }
+
+    /// Get the number of whole [`Duration::DELTA`]s in this duration.
+    pub fn deltas(&self) -> usize {
+        self.0 / Self::DELTA.0
+    }
+
     pub fn dotted(&self) -> Self {
         Self(self.0 + self.0 / 2)
     }
 
-    /// Decrement this duration by a sixteenth note. Returns `None` in place of
-    /// zero.
-    pub fn decrement(&self) -> Option<Self> {
-        if self.0 < 2 {
-            None
+    /// Get the length of each of `n` notes played in the time of `m` of this
+    /// duration, e.g. `tuplet(3, 2)` for a triplet. Rounded to the nearest
+    /// tick for tuplets that don't divide a quarter note evenly.
+    pub fn tuplet(&self, n: usize, m: usize) -> Self {
+        Self((self.0 * m + n / 2) / n.max(1))
+    }
+
+    /// Tie this duration to `other`, for a single note lasting both.
+    pub fn tie(&self, other: Self) -> Self {
+        *self + other
+    }
+}
+
+impl Display for Duration {
+    /// Write this duration as a number of [`Duration::DELTA`]s, as a fraction
+    /// if need be, e.g. `4` or `8/3`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gcd = gcd(self.0, Self::DELTA.0);
+        let (numerator, denominator) = (self.0 / gcd, Self::DELTA.0 / gcd);
+        if denominator == 1 {
+            f.pad(&numerator.to_string())
         } else {
-            Some(Self(self.0 - 1))
+            f.pad(&format!("{numerator}/{denominator}"))
+        }
+    }
+}
+
+impl FromStr for Duration {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid duration '{s}' (expected a number like 4 or 8/3)");
+        let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+        let numerator: usize = numerator.parse().map_err(|_| invalid())?;
+        let denominator: usize = denominator.parse().map_err(|_| invalid())?;
+
+        let ticks = numerator.checked_mul(Self::DELTA.0).ok_or_else(invalid)?;
+        if denominator == 0 || !ticks.is_multiple_of(denominator) {
+            bail!("duration '{s}' is not a whole number of ticks");
         }
+
+        Ok(Self(ticks / denominator))
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
@@ -51,6 +120,14 @@ impl AddAssign for Duration {
     }
 }
 
+impl Sub for Duration {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
 impl Mul<usize> for Duration {
     type Output = Self;
 
@@ -72,3 +149,29 @@ impl MulAssign<usize> for Duration {
         self.0 *= rhs;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Duration;
+
+    #[test]
+    fn tuplets_and_text() {
+        let triplet = Duration::EIGHT.tuplet(3, 2);
+        assert_eq!(Duration::QUARTER, 3 * triplet);
+        assert_eq!(Duration::QUARTER, 5 * Duration::SIXTEENTH.tuplet(5, 4));
+        assert_eq!(
+            Duration::QUARTER + Duration::SIXTEENTH,
+            Duration::QUARTER.tie(Duration::SIXTEENTH)
+        );
+
+        for duration in [Duration::WHOLE, Duration::DELTA, triplet, Duration::TICK] {
+            assert_eq!(duration, duration.to_string().parse().unwrap());
+        }
+
+        assert_eq!("4", Duration::EIGHT.to_string());
+        assert_eq!("8/3", triplet.to_string());
+        assert!("1/7".parse::<Duration>().is_err());
+        assert!("x".parse::<Duration>().is_err());
+        assert!(usize::MAX.to_string().parse::<Duration>().is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use super::{Pitch, Scales};
+use crate::bytes::NibbleStream;
+use crate::session::Session;
+
+/// The key a performance is in: the pitch every melody is relative to, and
+/// the scales melodies draw their pitches from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Key {
+    /// The key the performance started in, which key changes stay within
+    /// half a period of.
+    home: Pitch,
+
+    base: Pitch,
+    scales: Scales,
+
+    /// Whether the last relative move went to the relative key, so the next
+    /// one goes back.
+    relative: bool,
+}
+
+impl Key {
+    pub fn new(base: Pitch, scales: Scales) -> Self {
+        Self {
+            home: base,
+            base,
+            scales,
+            relative: false,
+        }
+    }
+
+    pub fn home(&self) -> Pitch {
+        self.home
+    }
+
+    pub fn base(&self) -> Pitch {
+        self.base
+    }
+
+    pub fn scales(&self) -> &Scales {
+        &self.scales
+    }
+
+    /// Move this key, along with its home, by the given number of semitones.
+    pub fn transpose(&mut self, semitones: i32) {
+        self.home = self.home + semitones;
+        self.base = self.base + semitones;
+    }
+
+    /// Change to another key.
+    pub fn change(&mut self, change: KeyChange) {
+        match change {
+            KeyChange::FifthUp => self.set_base(self.base + self.fifth()),
+            KeyChange::FifthDown => self.set_base(self.base + -self.fifth()),
+            KeyChange::Relative => {
+                // between minor and major, the relative key starts on the
+                // third degree, so go up two degrees of the melody scale, or
+                // back down to where we came from
+                let degrees = if self.relative { -2 } else { 2 };
+                let base = self.scales.melody.pitch(self.base, degrees);
+
+                // the notes must still be in the scale of the text, or this
+                // would be a plain transposition
+                let Some(notes) = self.scales.notes.degree(self.base, base) else {
+                    return;
+                };
+
+                self.scales = Scales {
+                    notes: self.scales.notes.rotate(notes as i32),
+                    melody: self.scales.melody.rotate(degrees),
+                };
+                self.relative = !self.relative;
+                self.set_base(base);
+            }
+        }
+    }
+
+    /// Get the number of steps closest to a perfect fifth, 7 when the scales
+    /// repeat every 12. Periods other than 12 are taken to divide an octave
+    /// equally.
+    fn fifth(&self) -> i32 {
+        let period = self.scales.notes.period() as f64;
+        (period * 1.5f64.log2()).round() as i32
+    }
+
+    /// Move to `base`, or the same pitch class within half a period of home.
+    fn set_base(&mut self, base: Pitch) {
+        let period = self.scales.notes.period();
+        let half = period / 2;
+        self.base = self.home + (((base - self.home) + half).rem_euclid(period) - half);
+    }
+
+    pub fn save(&self, session: &mut Session) {
+        session.set("key.base", self.base);
+        session.set("key.scale", &self.scales.notes);
+        session.set("key.melody-scale", &self.scales.melody);
+        session.set("key.relative", self.relative);
+    }
+
+    /// Restore the current key from a session, keeping the home this key was
+    /// created with.
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let base = session.get("key.base")?;
+        let scales = Scales {
+            notes: session.get("key.scale")?,
+            melody: session.get("key.melody-scale")?,
+        };
+        let relative = session.get("key.relative")?;
+
+        self.base = base;
+        self.scales = scales;
+        self.relative = relative;
+        Ok(())
+    }
+}
+
+impl Display for Key {
+    /// Write this key as its base and melody scale, e.g. `A2 minor`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.scales.melody)
+    }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self::new(Pitch::A2, Scales::default())
+    }
+}
+
+/// A move from one key to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyChange {
+    /// Up a fifth along the circle of fifths.
+    FifthUp,
+
+    /// Down a fifth along the circle of fifths.
+    FifthDown,
+
+    /// To the relative major or minor, or back.
+    Relative,
+}
+
+/// The kinds of key change the text can make.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyMoves {
+    /// Only moves along the circle of fifths.
+    Fifths,
+
+    /// Only moves between relative keys.
+    Relative,
+
+    /// Either.
+    Mixed,
+}
+
+impl KeyMoves {
+    /// Pick a key change, or none at all, from a nibble.
+    pub fn pick(self, nibble: u8) -> Option<KeyChange> {
+        // stay in key a quarter of the time
+        if nibble < 4 {
+            return None;
+        }
+
+        Some(match self {
+            Self::Fifths if nibble.is_multiple_of(2) => KeyChange::FifthUp,
+            Self::Fifths => KeyChange::FifthDown,
+            Self::Relative => KeyChange::Relative,
+            Self::Mixed => match nibble % 3 {
+                0 => KeyChange::FifthUp,
+                1 => KeyChange::FifthDown,
+                _ => KeyChange::Relative,
+            },
+        })
+    }
+}
+
+impl Display for KeyMoves {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fifths => write!(f, "fifths"),
+            Self::Relative => write!(f, "relative"),
+            Self::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+impl FromStr for KeyMoves {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "fifths" => Self::Fifths,
+            "relative" => Self::Relative,
+            "mixed" => Self::Mixed,
+            _ => bail!("unknown key moves '{s}' (expected fifths, relative or mixed)"),
+        })
+    }
+}
+
+/// Key changes picked by the text every so many bars.
+#[derive(Clone, Debug)]
+pub struct Modulation {
+    moves: KeyMoves,
+    bars: usize,
+    nibbles: NibbleStream<1>,
+}
+
+impl Modulation {
+    pub fn new(input: &[u8], moves: KeyMoves, bars: usize) -> Self {
+        Self {
+            moves,
+            bars,
+            nibbles: NibbleStream::new(input),
+        }
+    }
+
+    /// Get the key change due at the start of the given bar, if any.
+    pub fn update(&mut self, bar: usize) -> Option<KeyChange> {
+        if bar == 0 || !bar.is_multiple_of(self.bars.max(1)) {
+            return None;
+        }
+
+        self.moves.pick(self.nibbles.next_nibble())
+    }
+
+    pub fn update_input(&mut self, input: &[u8]) {
+        self.nibbles = self.nibbles.with_new_data(input);
+    }
+
+    pub fn save(&self, session: &mut Session) {
+        session.set_stream("modulation.nibbles", &self.nibbles);
+    }
+
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        session.get_stream("modulation.nibbles", &mut self.nibbles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, KeyChange, KeyMoves};
+    use crate::notes::{Pitch, Scale, Scales};
+
+    #[test]
+    fn fifths_and_relative_keys() {
+        let mut key = Key::default();
+        let home = key.base();
+
+        // around the circle of fifths, staying near home
+        for _ in 0..12 {
+            key.change(KeyChange::FifthUp);
+            assert!((-6..6).contains(&(key.base() - home)));
+        }
+        assert_eq!(home, key.base());
+
+        key.change(KeyChange::FifthDown);
+        assert_eq!(Pitch::from_midi(50), key.base());
+        key.change(KeyChange::FifthUp);
+
+        // A minor to C major, and back
+        key.change(KeyChange::Relative);
+        assert_eq!(Pitch::from_midi(48), key.base());
+        assert_eq!(Scale::MAJOR, key.scales().melody);
+        assert_eq!(Scale::PENTATONIC_MAJOR, key.scales().notes);
+
+        key.change(KeyChange::Relative);
+        assert_eq!(Key::default(), key);
+
+        // two whole tones up is not in the minor pentatonic
+        let mut key = Key::new(
+            Pitch::A2,
+            Scales {
+                notes: Scale::PENTATONIC_MINOR,
+                melody: Scale::WHOLE_TONE,
+            },
+        );
+        let before = key.clone();
+        key.change(KeyChange::Relative);
+        assert_eq!(before, key);
+
+        // a fifth is 11 steps of 19 equal divisions of the octave
+        let nineteen = Scale::new(vec![3, 3, 2, 3, 3, 3, 2]).unwrap();
+        let mut key = Key::new(Pitch::A2, Scales::uniform(nineteen));
+        key.change(KeyChange::FifthUp);
+        assert_eq!(Pitch::A2 + -8, key.base());
+        for _ in 1..19 {
+            key.change(KeyChange::FifthUp);
+            assert!((-9..=9).contains(&(key.base() - Pitch::A2)));
+        }
+        assert_eq!(Pitch::A2, key.base());
+
+        assert_eq!(None, KeyMoves::Mixed.pick(3));
+        assert_eq!(Ok(KeyMoves::Relative), "relative".parse().map_err(|_| ()));
+    }
+}
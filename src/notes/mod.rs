@@ -1,38 +1,87 @@
 mod duration;
+mod key;
 mod pitch;
+mod scale;
+mod tuning;
 
 pub use self::duration::Duration;
-pub use self::pitch::{Octave, Pitch};
+pub use self::key::{Key, KeyChange, KeyMoves, Modulation};
+pub use self::pitch::{Detuned, Pitch};
+pub use self::scale::{Scale, Scales};
+pub use self::tuning::{KeyboardMapping, Tuning};
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
 
 use crate::bytes::NibbleStream;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Note {
     pub pitch: Option<Pitch>,
     pub duration: Duration,
 }
 
+impl Display for Note {
+    /// Write this note as its MIDI note number, or `-` for a rest, and its
+    /// length in thirty-second notes, e.g. `45:4` or `45:8/3`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pitch {
+            Some(pitch) => write!(f, "{}:{}", pitch.midi(), self.duration),
+            None => write!(f, "-:{}", self.duration),
+        }
+    }
+}
+
+impl FromStr for Note {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pitch, duration) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected a note like '45:4', not '{s}'"))?;
+
+        let pitch = match pitch {
+            "-" => None,
+            midi => Some(midi.parse()?),
+        };
+
+        Ok(Self {
+            pitch,
+            duration: duration.parse()?,
+        })
+    }
+}
+
 impl NibbleStream<3> {
-    pub fn next_note(&mut self, base: Pitch) -> Note {
+    /// Get a note in `scale` starting at `base`.
+    pub fn next_note(&mut self, base: Pitch, scale: &Scale) -> Note {
         let [a, b, c] = self.next_nibbles();
         let noisy = a != 0;
 
         let mut duration = Duration::SIXTEENTH;
 
-        if b & 8 != 0 {
-            duration *= 3;
-        }
-
         if b & 4 != 0 {
             duration *= 2;
         }
 
+        // a triplet of notes four times as long, for about three times the
+        // length
+        if b & 8 != 0 {
+            duration = (4 * duration).tuplet(3, 2);
+        }
+
         if b & 1 != 0 {
             duration = duration.dotted();
         }
 
+        if b & 2 != 0 {
+            duration = duration.tie(Duration::SIXTEENTH);
+        }
+
         let pitch = (c & 7) as i32 + if c & 8 != 0 { -8 } else { 0 };
-        let pitch = base.in_pentatonic_minor(pitch);
+        let pitch = scale.pitch(base, pitch);
 
         Note {
             pitch: noisy.then_some(pitch),
@@ -1,40 +1,53 @@
+use std::fmt::Display;
 use std::ops::Add;
 use std::ops::Sub;
+use std::str::FromStr;
 
-pub const PENTATONIC_MINOR: [i32; 5] = [3, 2, 2, 3, 2];
-pub const MINOR: [i32; 7] = [2, 1, 2, 2, 1, 2, 2];
+use anyhow::{anyhow, bail, Context};
 
 /// The base frequency, in Hertz, of A4.
 const A4: f64 = 440.0;
 const TWELFTH_ROOT_TWO: f64 = 1.059_463_094_359_295_3;
 
+/// The names of the pitch classes starting from C, spelled with sharps.
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
 /// A pitch is an exponential frequency. Represented as a semi-tone offset from
 /// A4.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Pitch(i32);
 
 impl Pitch {
     pub const A2: Pitch = Pitch(-24);
 
-    /// Get the frequency, in Hz, of this pitch.
-    pub fn as_frequency(&self) -> f64 {
-        A4 * (TWELFTH_ROOT_TWO.powi(self.0))
-    }
+    /// The MIDI note number of A4.
+    const A4_MIDI: i32 = 69;
 
-    pub fn minor_scale_number(&self, pitch: Pitch) -> Option<usize> {
-        self.scale_number(pitch, &MINOR)
+    /// Get the pitch with the given MIDI note number.
+    pub fn from_midi(note: i32) -> Pitch {
+        Pitch(note - Self::A4_MIDI)
     }
 
-    pub fn pentatonic_minor_scale_number(&self, pitch: Pitch) -> Option<usize> {
-        self.scale_number(pitch, &PENTATONIC_MINOR)
+    /// Get the pitch with the given MIDI note number, or `None` if it is too
+    /// low to be represented.
+    fn checked_from_midi(note: i32) -> Option<Pitch> {
+        note.checked_sub(Self::A4_MIDI).map(Pitch)
     }
 
-    pub fn in_minor(&self, n: i32) -> Pitch {
-        self.in_scale(n, &MINOR)
+    /// Get the MIDI note number of this pitch. May lie outside the usual range
+    /// of `0..=127`.
+    pub fn midi(&self) -> i32 {
+        self.0 + Self::A4_MIDI
     }
 
-    pub fn in_pentatonic_minor(&self, n: i32) -> Pitch {
-        self.in_scale(n, &PENTATONIC_MINOR)
+    /// Get the frequency, in Hz, of this pitch in twelve tone equal
+    /// temperament with A4 at 440 Hz. See [`Tuning`] for other tunings.
+    ///
+    /// [`Tuning`]: super::Tuning
+    pub fn as_frequency(&self) -> f64 {
+        A4 * (TWELFTH_ROOT_TWO.powi(self.0))
     }
 
     /// Get the pitch that is the `n`th note in the given scale with this note
@@ -59,19 +72,21 @@ impl Pitch {
 
     /// Get the scale number for the given pitch in the scale with this one as
     /// its base note.
-    pub fn scale_number(&self, mut pitch: Pitch, scale: &[i32]) -> Option<usize> {
-        pitch.0 -= 12 * ((pitch.0 - 12) / 12);
+    pub fn scale_number(&self, pitch: Pitch, scale: &[i32]) -> Option<usize> {
+        // the scale repeats every period, so only the pitch class above the
+        // base matters
+        let target = (pitch.0 - self.0).rem_euclid(scale.iter().sum());
 
         let mut step_index = 0;
-        let mut pitch_offset = self.0;
+        let mut pitch_offset = 0;
 
-        while pitch_offset < pitch.0 {
+        while pitch_offset < target {
             let step = scale[step_index];
-            step_index = step_index.wrapping_add(1) % scale.len();
+            step_index = (step_index + 1) % scale.len();
             pitch_offset += step;
         }
 
-        if pitch_offset == pitch.0 {
+        if pitch_offset == target {
             Some(step_index)
         } else {
             None
@@ -79,6 +94,111 @@ impl Pitch {
     }
 }
 
+impl Display for Pitch {
+    /// Write this pitch in scientific pitch notation, spelled with sharps and
+    /// with C4 as middle C, e.g. `C#4`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let midi = self.midi();
+        let name = NAMES[midi.rem_euclid(12) as usize];
+        f.pad(&format!("{name}{}", midi.div_euclid(12) - 1))
+    }
+}
+
+impl FromStr for Pitch {
+    type Err = anyhow::Error;
+
+    /// Parse a pitch either in scientific pitch notation, like `A2`, `C#4` or
+    /// `Eb-1`, or as a MIDI note number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid pitch '{s}' (expected a note like C#4 or Eb2)");
+
+        if let Ok(midi) = s.parse() {
+            return Self::checked_from_midi(midi).ok_or_else(invalid);
+        }
+
+        let mut chars = s.chars();
+        let letter = chars.next().ok_or_else(invalid)?.to_ascii_uppercase();
+        let mut semitones = match letter {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return Err(invalid()),
+        };
+
+        let rest = chars.as_str();
+        let octave = rest.trim_start_matches(['#', 'b']);
+        for accidental in rest[..rest.len() - octave.len()].chars() {
+            semitones += if accidental == '#' { 1 } else { -1 };
+        }
+
+        let octave: i32 = octave.parse().map_err(|_| invalid())?;
+        octave
+            .checked_add(1)
+            .and_then(|octave| octave.checked_mul(12))
+            .and_then(|midi| midi.checked_add(semitones))
+            .and_then(Self::checked_from_midi)
+            .ok_or_else(invalid)
+    }
+}
+
+/// A pitch detuned by some cents, for when a whole semitone is too coarse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detuned {
+    pub pitch: Pitch,
+    pub cents: f64,
+}
+
+impl Detuned {
+    /// Get the frequency, in Hz, of this pitch in twelve tone equal
+    /// temperament with A4 at 440 Hz.
+    pub fn as_frequency(&self) -> f64 {
+        self.pitch.as_frequency() * 2f64.powf(self.cents / 1200.0)
+    }
+}
+
+impl Display for Detuned {
+    /// Write this pitch like a [`Pitch`], followed by the cents it is detuned
+    /// by if any, e.g. `A4+25c`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.cents == 0.0 {
+            write!(f, "{}", self.pitch)
+        } else {
+            write!(f, "{}{:+}c", self.pitch, self.cents)
+        }
+    }
+}
+
+impl FromStr for Detuned {
+    type Err = anyhow::Error;
+
+    /// Parse a pitch like a [`Pitch`], optionally followed by the cents it is
+    /// detuned by, like `A4+25c` or `C3-3.5c`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(rest) = s.strip_suffix('c') else {
+            return Ok(Self {
+                pitch: s.parse()?,
+                cents: 0.0,
+            });
+        };
+
+        let Some(split) = rest.rfind(['+', '-']).filter(|split| *split > 0) else {
+            bail!("invalid pitch '{s}' (expected a note like A4+25c)");
+        };
+
+        let (pitch, cents) = rest.split_at(split);
+        Ok(Self {
+            pitch: pitch.parse()?,
+            cents: cents
+                .parse()
+                .with_context(|| format!("invalid cents in '{s}'"))?,
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Octave;
 
@@ -107,6 +227,15 @@ impl Add<Pitch> for Octave {
     }
 }
 
+impl Sub for Pitch {
+    type Output = i32;
+
+    /// Get the number of semitones from `rhs` up to this pitch.
+    fn sub(self, rhs: Pitch) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
 impl Sub<Octave> for Pitch {
     type Output = Self;
 
@@ -117,26 +246,61 @@ impl Sub<Octave> for Pitch {
 
 #[cfg(test)]
 mod tests {
-    use super::{Pitch, PENTATONIC_MINOR};
+    use super::{Detuned, Pitch};
+    use crate::notes::Scale;
+
+    #[test]
+    fn names() {
+        for midi in -12..140 {
+            let pitch = Pitch::from_midi(midi);
+            assert_eq!(pitch, pitch.to_string().parse().unwrap());
+        }
+
+        assert_eq!("A2", Pitch::A2.to_string());
+        assert_eq!("C4", Pitch::from_midi(60).to_string());
+        assert_eq!("C-1", Pitch::from_midi(0).to_string());
+        assert_eq!(Pitch::from_midi(61), "C#4".parse().unwrap());
+        assert_eq!(Pitch::from_midi(39), "Eb2".parse().unwrap());
+        assert_eq!(Pitch::from_midi(48), "B#2".parse().unwrap());
+        assert_eq!(Pitch::from_midi(45), "45".parse().unwrap());
+        assert!("H2".parse::<Pitch>().is_err());
+        assert!("C".parse::<Pitch>().is_err());
+        assert!("-2147483648".parse::<Pitch>().is_err());
+        assert!("C178956970".parse::<Pitch>().is_err());
+        assert!("Cb-178956971".parse::<Pitch>().is_err());
+
+        let detuned: Detuned = "A4+25c".parse().unwrap();
+        assert_eq!(Pitch::from_midi(69), detuned.pitch);
+        assert_eq!(25.0, detuned.cents);
+        assert_eq!("A4+25c", detuned.to_string());
+        assert_eq!(-1.5, "Bb-1-1.5c".parse::<Detuned>().unwrap().cents);
+        assert_eq!("C3", "C3".parse::<Detuned>().unwrap().to_string());
+        assert!("+25c".parse::<Detuned>().is_err());
+    }
 
     #[test]
     fn scale_number_two_way() {
-        let base = Pitch::A2;
-        let scale = &PENTATONIC_MINOR;
+        let scale = Scale::PENTATONIC_MINOR;
+        let scale = scale.steps();
 
-        for i in 0..scale.len() {
-            let pitch = base.in_scale(i as i32, scale);
-            let j = base.scale_number(pitch, scale);
+        // bases below and above A4
+        for base in [Pitch::A2, Pitch::from_midi(74), Pitch::from_midi(95)] {
+            for i in 0..scale.len() {
+                let pitch = base.in_scale(i as i32, scale);
+                let j = base.scale_number(pitch, scale);
 
-            assert_eq!(Some(i), j);
-        }
+                assert_eq!(Some(i), j);
+            }
+
+            for i in -60..=60 {
+                let pitch = base.in_scale(i, scale);
+                let j = base.scale_number(pitch, scale);
 
-        for i in -60..=60 {
-            let pitch = base.in_scale(i, scale);
-            let j = base.scale_number(pitch, scale);
+                let wrapped = i.rem_euclid(scale.len() as i32) as usize;
+                assert_eq!(Some(wrapped), j);
+            }
 
-            let wrapped = i.rem_euclid(scale.len() as i32) as usize;
-            assert_eq!(Some(wrapped), j);
+            assert_eq!(None, base.scale_number(base + 1, scale));
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+
+use super::Pitch;
+
+/// A scale, as the steps between its notes, adding up to the period it repeats
+/// at. Steps are counted in MIDI note numbers, which are semitones in the
+/// default tuning, so the built in scales span twelve of them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scale {
+    steps: Cow<'static, [i32]>,
+}
+
+impl Scale {
+    pub const MAJOR: Scale = Scale::from_static(&[2, 2, 1, 2, 2, 2, 1]);
+    pub const DORIAN: Scale = Scale::from_static(&[2, 1, 2, 2, 2, 1, 2]);
+    pub const PHRYGIAN: Scale = Scale::from_static(&[1, 2, 2, 2, 1, 2, 2]);
+    pub const LYDIAN: Scale = Scale::from_static(&[2, 2, 2, 1, 2, 2, 1]);
+    pub const MIXOLYDIAN: Scale = Scale::from_static(&[2, 2, 1, 2, 2, 1, 2]);
+    pub const MINOR: Scale = Scale::from_static(&[2, 1, 2, 2, 1, 2, 2]);
+    pub const LOCRIAN: Scale = Scale::from_static(&[1, 2, 2, 1, 2, 2, 2]);
+    pub const HARMONIC_MINOR: Scale = Scale::from_static(&[2, 1, 2, 2, 1, 3, 1]);
+    pub const MELODIC_MINOR: Scale = Scale::from_static(&[2, 1, 2, 2, 2, 2, 1]);
+    pub const WHOLE_TONE: Scale = Scale::from_static(&[2, 2, 2, 2, 2, 2]);
+    pub const PENTATONIC_MAJOR: Scale = Scale::from_static(&[2, 2, 3, 2, 3]);
+    pub const PENTATONIC_MINOR: Scale = Scale::from_static(&[3, 2, 2, 3, 2]);
+    pub const BLUES: Scale = Scale::from_static(&[3, 2, 1, 1, 3, 2]);
+    pub const CHROMATIC: Scale = Scale::from_static(&[1; 12]);
+
+    /// The named scales, with the name each is written as first. Later names
+    /// for the same steps are aliases.
+    pub const LIBRARY: [(&'static str, Scale); 16] = [
+        ("major", Self::MAJOR),
+        ("ionian", Self::MAJOR),
+        ("dorian", Self::DORIAN),
+        ("phrygian", Self::PHRYGIAN),
+        ("lydian", Self::LYDIAN),
+        ("mixolydian", Self::MIXOLYDIAN),
+        ("minor", Self::MINOR),
+        ("aeolian", Self::MINOR),
+        ("locrian", Self::LOCRIAN),
+        ("harmonic-minor", Self::HARMONIC_MINOR),
+        ("melodic-minor", Self::MELODIC_MINOR),
+        ("whole-tone", Self::WHOLE_TONE),
+        ("pentatonic-major", Self::PENTATONIC_MAJOR),
+        ("pentatonic-minor", Self::PENTATONIC_MINOR),
+        ("blues", Self::BLUES),
+        ("chromatic", Self::CHROMATIC),
+    ];
+
+    const fn from_static(steps: &'static [i32]) -> Self {
+        Self {
+            steps: Cow::Borrowed(steps),
+        }
+    }
+
+    /// Create a scale from the steps between its notes. Every step must be
+    /// positive, and together they make up the period of the scale, which
+    /// should match that of the tuning, see [`Tuning::period`].
+    ///
+    /// [`Tuning::period`]: super::Tuning::period
+    pub fn new(steps: Vec<i32>) -> anyhow::Result<Self> {
+        if steps.is_empty() || steps.iter().any(|step| *step <= 0) {
+            bail!("scale steps must be positive");
+        }
+
+        Ok(Self {
+            steps: Cow::Owned(steps),
+        })
+    }
+
+    pub fn steps(&self) -> &[i32] {
+        &self.steps
+    }
+
+    /// Get the number of steps after which this scale repeats, 12 for an
+    /// octave in the default tuning.
+    pub fn period(&self) -> i32 {
+        self.steps.iter().sum()
+    }
+
+    /// Get the name of this scale, if it is in the [`Scale::LIBRARY`].
+    pub fn name(&self) -> Option<&'static str> {
+        Self::LIBRARY
+            .iter()
+            .find(|(_, scale)| scale == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Get the `n`th note of this scale starting at `base`.
+    pub fn pitch(&self, base: Pitch, n: i32) -> Pitch {
+        base.in_scale(n, &self.steps)
+    }
+
+    /// Get the degree of `pitch` in this scale starting at `base`, if it is in
+    /// the scale at all.
+    pub fn degree(&self, base: Pitch, pitch: Pitch) -> Option<usize> {
+        base.scale_number(pitch, &self.steps)
+    }
+
+    /// Get the mode of this scale starting on its `degrees`th note.
+    pub fn rotate(&self, degrees: i32) -> Scale {
+        let mut steps = self.steps.to_vec();
+        let len = steps.len() as i32;
+        steps.rotate_left(degrees.rem_euclid(len) as usize);
+        Self {
+            steps: Cow::Owned(steps),
+        }
+    }
+
+    /// Get the triad stacked in thirds of this scale on `pitch`, as steps above
+    /// `base` within a single period, in ascending order.
+    pub fn triad(&self, base: Pitch, pitch: Pitch) -> Option<[i32; 3]> {
+        let degree = self.degree(base, pitch)? as i32;
+        let mut triad = [0, 2, 4]
+            .map(|third| (self.pitch(base, degree + third) - base).rem_euclid(self.period()));
+        triad.sort();
+        Some(triad)
+    }
+}
+
+impl Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name}"),
+            None => {
+                let steps: Vec<_> = self.steps.iter().map(i32::to_string).collect();
+                write!(f, "{}", steps.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for Scale {
+    type Err = anyhow::Error;
+
+    /// Parse a scale either by its name in the [`Scale::LIBRARY`], or as a
+    /// comma separated list of steps, like `2,1,2,2,1,2,2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, scale)) = Self::LIBRARY.iter().find(|(name, _)| *name == s) {
+            return Ok(scale.clone());
+        }
+
+        let steps = s
+            .split(',')
+            .map(|step| step.trim().parse())
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| {
+                let names: Vec<_> = Self::LIBRARY.iter().map(|(name, _)| *name).collect();
+                anyhow!(
+                    "unknown scale '{s}' (expected a list of steps like 2,1,2,2,1,2,2 or one \
+                     of {})",
+                    names.join(", ")
+                )
+            })?;
+
+        Self::new(steps)
+    }
+}
+
+/// The scales a performance draws its pitches from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scales {
+    /// The scale of the notes coming straight from the text, and of the roots
+    /// of arpeggios.
+    pub notes: Scale,
+
+    /// The scale melodies move along, and arpeggios are built from.
+    pub melody: Scale,
+}
+
+impl Scales {
+    /// Use the same scale for everything.
+    pub fn uniform(scale: Scale) -> Self {
+        Self {
+            notes: scale.clone(),
+            melody: scale,
+        }
+    }
+}
+
+impl Default for Scales {
+    /// Pentatonic minor notes, over natural minor melodies.
+    fn default() -> Self {
+        Self {
+            notes: Scale::PENTATONIC_MINOR,
+            melody: Scale::MINOR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scale;
+    use crate::notes::Pitch;
+
+    #[test]
+    fn parse_and_triads() {
+        for (name, scale) in Scale::LIBRARY {
+            assert_eq!(scale.steps().iter().sum::<i32>(), 12, "{name}");
+            assert_eq!(Ok(scale.clone()), name.parse().map_err(|_| ()));
+            assert_eq!(Ok(scale.clone()), scale.to_string().parse().map_err(|_| ()));
+        }
+
+        assert_eq!(Scale::DORIAN, "2,1,2,2,2,1,2".parse().unwrap());
+        assert_eq!("2,2,8", "2,2,8".parse::<Scale>().unwrap().to_string());
+        assert_eq!(6, "2,2,2".parse::<Scale>().unwrap().period());
+        assert!("2,0,2".parse::<Scale>().is_err());
+        assert!("lydian-ish".parse::<Scale>().is_err());
+
+        // the chords of the pentatonic minor notes, taken from the natural
+        // minor scale
+        let base = Pitch::A2;
+        let triads: Vec<_> = (0..5)
+            .map(|n| {
+                let pitch = Scale::PENTATONIC_MINOR.pitch(base, n);
+                Scale::MINOR.triad(base, pitch).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            vec![[0, 3, 7], [3, 7, 10], [0, 5, 8], [2, 7, 10], [2, 5, 10]],
+            triads
+        );
+    }
+}
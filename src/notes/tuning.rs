@@ -0,0 +1,438 @@
+//! Mapping pitches to frequencies.
+//!
+//! A [`Tuning`] is made up of a scale of intervals, given in cents above its
+//! root with the last one being the period it repeats at, and a
+//! [`KeyboardMapping`] which assigns the degrees of that scale to MIDI note
+//! numbers. Both can be read from Scala `.scl` and `.kbm` files.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use super::Pitch;
+use crate::session::{self, Session};
+
+/// The number of cents in an octave.
+const OCTAVE: f64 = 1200.0;
+
+/// A way of mapping pitches to frequencies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    /// The intervals of the scale above its root, in cents. The last one is
+    /// the period the scale repeats at.
+    cents: Vec<f64>,
+
+    pub mapping: KeyboardMapping,
+}
+
+/// Which scale degree each MIDI note number plays, and the frequency of one of
+/// them. See the Scala `.kbm` file format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyboardMapping {
+    /// The lowest and highest MIDI note numbers that sound.
+    pub first: i32,
+    pub last: i32,
+
+    /// The MIDI note number the root of the scale is mapped to.
+    pub middle: i32,
+
+    /// The MIDI note number tuned to `frequency`.
+    pub reference: i32,
+
+    /// The frequency of `reference`, in Hz.
+    pub frequency: f64,
+
+    /// The scale degree which every repetition of `keys` moves up by.
+    pub period_degree: usize,
+
+    /// The scale degree of each key, starting at `middle` and repeating, or
+    /// `None` if the key is silent. If empty, consecutive keys play
+    /// consecutive degrees.
+    pub keys: Vec<Option<usize>>,
+}
+
+impl Tuning {
+    /// Create a tuning from the intervals of its scale above the root, in
+    /// cents, with the last one being the period. Uses a linear mapping with
+    /// A4 at 440 Hz.
+    pub fn new(cents: Vec<f64>) -> anyhow::Result<Self> {
+        match cents.last() {
+            Some(period) if *period > 0.0 => {}
+            _ => bail!("a tuning needs a positive period"),
+        }
+
+        Ok(Self {
+            cents,
+            mapping: KeyboardMapping::default(),
+        })
+    }
+
+    /// Create a tuning which divides the octave into `divisions` equal steps,
+    /// one per MIDI note number.
+    pub fn equal(divisions: usize) -> anyhow::Result<Self> {
+        if divisions == 0 {
+            bail!("an equal tuning needs at least one division");
+        }
+
+        let step = OCTAVE / divisions as f64;
+        Self::new((1..=divisions).map(|n| n as f64 * step).collect())
+    }
+
+    /// Read a tuning from a Scala `.scl` file, with a linear mapping.
+    pub fn read_scl(path: &Path) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("could not read '{}'", path.display()))?;
+        Self::from_scl(&source).with_context(|| format!("in '{}'", path.display()))
+    }
+
+    /// Parse the contents of a Scala `.scl` file.
+    pub fn from_scl(source: &str) -> anyhow::Result<Self> {
+        let mut lines = source.lines().filter(|line| !line.starts_with('!'));
+
+        let _description = lines.next().ok_or_else(|| anyhow!("missing description"))?;
+        let count: usize = first_word(lines.next())
+            .ok_or_else(|| anyhow!("missing number of notes"))?
+            .parse()
+            .context("invalid number of notes")?;
+
+        let cents = lines
+            .take(count)
+            .map(|line| {
+                let word = first_word(Some(line)).unwrap_or("");
+                parse_interval(word).with_context(|| format!("invalid interval '{word}'"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if cents.len() != count {
+            bail!("expected {count} notes, found {}", cents.len());
+        }
+
+        Self::new(cents)
+    }
+
+    /// Get the number of degrees in a period of the scale.
+    pub fn degrees(&self) -> usize {
+        self.cents.len()
+    }
+
+    /// Get the number of MIDI note numbers after which the mapped pitches
+    /// repeat a period higher. [`Scale`]s must add up to this many steps for
+    /// their notes to repeat along with them.
+    ///
+    /// [`Scale`]: super::Scale
+    pub fn period(&self) -> usize {
+        if self.mapping.keys.is_empty() {
+            self.degrees()
+        } else {
+            self.mapping.keys.len()
+        }
+    }
+
+    /// Get the intervals of the scale above its root, in cents.
+    pub fn cents(&self) -> &[f64] {
+        &self.cents
+    }
+
+    /// Get the frequency of `pitch` in Hz, or `None` if it is not mapped to
+    /// anything.
+    pub fn frequency(&self, pitch: Pitch) -> Option<f64> {
+        let key = pitch.midi();
+        if key < self.mapping.first || key > self.mapping.last {
+            return None;
+        }
+
+        let degree = self.mapping.degree(key, self.degrees())?;
+        let reference = self
+            .mapping
+            .degree(self.mapping.reference, self.degrees())
+            .unwrap_or((self.mapping.reference - self.mapping.middle) as i64);
+
+        let cents = self.cents_of(degree) - self.cents_of(reference);
+        Some(self.mapping.frequency * 2f64.powf(cents / OCTAVE))
+    }
+
+    /// Get the interval of the given degree above the root, in cents.
+    fn cents_of(&self, degree: i64) -> f64 {
+        let len = self.cents.len() as i64;
+        let period = self.cents[self.cents.len() - 1];
+        let step = match degree.rem_euclid(len) {
+            0 => 0.0,
+            n => self.cents[n as usize - 1],
+        };
+
+        degree.div_euclid(len) as f64 * period + step
+    }
+
+    /// Save this tuning.
+    pub fn save(&self, session: &mut Session) {
+        for cents in &self.cents {
+            session.set("tuning.cents", cents);
+        }
+
+        let KeyboardMapping {
+            first,
+            last,
+            middle,
+            reference,
+            frequency,
+            period_degree,
+            keys,
+        } = &self.mapping;
+
+        session.set(
+            "tuning.mapping",
+            format!("{first} {last} {middle} {reference} {frequency} {period_degree}"),
+        );
+
+        for key in keys {
+            match key {
+                Some(degree) => session.set("tuning.key", degree),
+                None => session.set("tuning.key", "x"),
+            }
+        }
+    }
+
+    /// Load a tuning saved by [`Tuning::save`].
+    pub fn load(session: &Session) -> anyhow::Result<Self> {
+        let cents = session
+            .get_all("tuning.cents")
+            .map(|cents| {
+                cents
+                    .parse()
+                    .with_context(|| format!("invalid cents '{cents}'"))
+            })
+            .collect::<anyhow::Result<Vec<f64>>>()?;
+
+        let mut tuning = Self::new(cents)?;
+
+        let [first, last, middle, reference, frequency, period_degree] =
+            session::fields::<String, 6>(session.value("tuning.mapping")?)
+                .context("in 'tuning.mapping'")?;
+
+        let keys = session
+            .get_all("tuning.key")
+            .map(parse_key)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let frequency: f64 = frequency.parse().context("in 'tuning.mapping'")?;
+        if !(frequency.is_finite() && frequency > 0.0) {
+            bail!("the reference frequency must be positive");
+        }
+
+        let field = |value: String| value.parse().context("in 'tuning.mapping'");
+        tuning.mapping = KeyboardMapping {
+            first: field(first)?,
+            last: field(last)?,
+            middle: field(middle)?,
+            reference: field(reference)?,
+            frequency,
+            period_degree: period_degree.parse().context("in 'tuning.mapping'")?,
+            keys,
+        };
+
+        Ok(tuning)
+    }
+}
+
+impl Default for Tuning {
+    /// Twelve tone equal temperament, with A4 at 440 Hz.
+    fn default() -> Self {
+        Self::equal(12).unwrap()
+    }
+}
+
+impl KeyboardMapping {
+    /// Read a mapping from a Scala `.kbm` file.
+    pub fn read_kbm(path: &Path) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("could not read '{}'", path.display()))?;
+        source
+            .parse()
+            .with_context(|| format!("in '{}'", path.display()))
+    }
+
+    /// Get the degree the given key plays, counting from the root at
+    /// `middle`, in a scale with `degrees` degrees per period.
+    fn degree(&self, key: i32, degrees: usize) -> Option<i64> {
+        let offset = (key - self.middle) as i64;
+        if self.keys.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.keys.len() as i64;
+        let period_degree = match self.period_degree {
+            0 => degrees,
+            n => n,
+        };
+
+        let degree = self.keys[offset.rem_euclid(size) as usize]?;
+        Some(offset.div_euclid(size) * period_degree as i64 + degree as i64)
+    }
+}
+
+impl Default for KeyboardMapping {
+    /// A linear mapping over every key, with the root at middle C and A4 at
+    /// 440 Hz.
+    fn default() -> Self {
+        Self {
+            first: i32::MIN,
+            last: i32::MAX,
+            middle: 60,
+            reference: 69,
+            frequency: 440.0,
+            period_degree: 0,
+            keys: vec![],
+        }
+    }
+}
+
+impl FromStr for KeyboardMapping {
+    type Err = anyhow::Error;
+
+    /// Parse the contents of a Scala `.kbm` file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .filter(|line| !line.starts_with('!'))
+            .map(|line| first_word(Some(line)).unwrap_or(""));
+
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .ok_or_else(|| anyhow!("missing {name}"))
+                .map(str::to_string)
+        };
+
+        let size: usize = field("map size")?.parse().context("invalid map size")?;
+        let first = field("first note")?.parse().context("invalid first note")?;
+        let last = field("last note")?.parse().context("invalid last note")?;
+        let middle = field("middle note")?
+            .parse()
+            .context("invalid middle note")?;
+        let reference = field("reference note")?
+            .parse()
+            .context("invalid reference note")?;
+        let frequency: f64 = field("reference frequency")?
+            .parse()
+            .context("invalid reference frequency")?;
+        let period_degree = field("octave degree")?
+            .parse()
+            .context("invalid octave degree")?;
+
+        if !(frequency.is_finite() && frequency > 0.0) {
+            bail!("the reference frequency must be positive");
+        }
+
+        // trailing unmapped keys may be left out
+        let keys = (0..size)
+            .map(|_| match lines.next() {
+                Some(key) if !key.is_empty() => parse_key(key),
+                _ => Ok(None),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            first,
+            last,
+            middle,
+            reference,
+            frequency,
+            period_degree,
+            keys,
+        })
+    }
+}
+
+fn first_word(line: Option<&str>) -> Option<&str> {
+    line?.split_whitespace().next()
+}
+
+/// Parse a Scala interval, either in cents if it contains a period, or as a
+/// ratio like `3/2` or `2`.
+fn parse_interval(word: &str) -> anyhow::Result<f64> {
+    if word.contains('.') {
+        return Ok(word.parse()?);
+    }
+
+    let (num, den) = word.split_once('/').unwrap_or((word, "1"));
+    let (num, den): (f64, f64) = (num.parse()?, den.parse()?);
+    if num <= 0.0 || den <= 0.0 {
+        bail!("ratios must be positive");
+    }
+
+    Ok(OCTAVE * (num / den).log2())
+}
+
+/// Parse a key of a mapping, either a degree or `x` for a silent key.
+fn parse_key(key: &str) -> anyhow::Result<Option<usize>> {
+    match key {
+        "x" | "X" => Ok(None),
+        degree => Ok(Some(
+            degree
+                .parse()
+                .with_context(|| format!("invalid key '{degree}'"))?,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyboardMapping, Tuning};
+    use crate::notes::Pitch;
+    use crate::session::Session;
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.unwrap();
+        assert!((expected - actual).abs() < 1e-6, "{expected} != {actual}");
+    }
+
+    #[test]
+    fn equal_and_scala() {
+        let tet = Tuning::default();
+        for midi in [21, 45, 60, 69, 100] {
+            let pitch = Pitch::from_midi(midi);
+            assert_close(pitch.as_frequency(), tet.frequency(pitch));
+        }
+
+        // 24-EDO puts the octave above A4 24 keys up
+        let edo = Tuning::equal(24).unwrap();
+        assert_close(880.0, edo.frequency(Pitch::from_midi(69 + 24)));
+        assert_eq!(24, edo.period());
+
+        let mut just = Tuning::from_scl(
+            "! just.scl\n\
+             just major\n\
+             7\n\
+             9/8\n5/4\n4/3\n3/2\n5/3\n15/8\n2/1\n",
+        )
+        .unwrap();
+
+        just.mapping = "! major.kbm\n\
+             7\n0\n127\n60\n60\n261.6\n7\n\
+             0\n1\n2\n3\n4\n5\n6\n"
+            .parse::<KeyboardMapping>()
+            .unwrap();
+        assert_close(261.6 * 1.5, just.frequency(Pitch::from_midi(64)));
+        assert_close(261.6 * 2.0, just.frequency(Pitch::from_midi(67)));
+        assert_eq!(7, just.period());
+        assert_eq!(None, just.frequency(Pitch::from_midi(128)));
+
+        let mut session = Session::new();
+        just.save(&mut session);
+        assert_eq!(just, Tuning::load(&session).unwrap());
+
+        let broken: Session = session
+            .to_string()
+            .replace(" 261.6 ", " NaN ")
+            .parse()
+            .unwrap();
+        assert!(Tuning::load(&broken).is_err());
+        assert!("0\n0\n127\n60\n69\ninf\n0\n"
+            .parse::<KeyboardMapping>()
+            .is_err());
+
+        assert!(Tuning::from_scl("bad\n2\n100.0\n").is_err());
+    }
+}
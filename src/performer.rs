@@ -1,87 +1,372 @@
+use std::collections::VecDeque;
+
+use log::debug;
+
 use crate::bytes::NibbleStream;
-use crate::envelope::AttackDecay;
+use crate::clock::Clock;
 use crate::float::Float;
-use crate::notes::{Duration, Pitch};
+use crate::notes::{Duration, Key, Modulation, Note, Tuning};
 use crate::sampler::Sampler;
-use crate::source::NoteSource;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::source::{NoteSource, Provenance};
 use crate::voice::VoiceGroup;
-use crate::wavetable::Wavetable;
+use crate::wavetable::asm::Program;
+use crate::wavetable::{Band, Initializer, Snapshot, Wavetable};
 
-pub const BASE: Pitch = Pitch::A2;
+/// The number of wavetable instructions that can be undone.
+pub const UNDO_DEPTH: usize = 64;
 
 pub struct Performer<const S: usize> {
     source: NoteSource,
+    key: Key,
+    modulation: Option<Modulation>,
+    tuning: Tuning,
 
     table: Wavetable<S>,
     table_nibbles: NibbleStream<1>,
+    program: Option<Program>,
+    band_limited: bool,
+    history: VecDeque<Snapshot<S>>,
+    frozen: bool,
 
     y: Float,
     y_nibbles: NibbleStream<5>,
 
     voices: VoiceGroup,
-    duration: Duration,
+    clock: Clock,
+
+    /// When the next note starts.
+    next_note: Duration,
+    last_note: Option<(Note, Provenance)>,
 }
 
 impl<const S: usize> Performer<S> {
-    pub fn new(input: &[u8], env: AttackDecay) -> Self {
+    pub fn new(input: &[u8], settings: &Settings) -> Self {
+        let mut table = initial_table(input, settings);
+        table.set_interpolation(settings.interpolation_x, settings.interpolation_y);
+
         Self {
-            source: NoteSource::new(input),
+            source: NoteSource::with_generators(input, &settings.registry, &settings.generators),
+            key: Key::new(settings.base, settings.scales.clone()),
+            modulation: settings
+                .key_changes
+                .map(|moves| Modulation::new(input, moves, settings.key_every)),
+            tuning: settings.tuning.clone(),
 
-            table: Wavetable::new_sine(),
+            table,
             table_nibbles: NibbleStream::new(input),
+            program: settings.program.clone(),
+            band_limited: settings.band_limited,
+            history: VecDeque::with_capacity(UNDO_DEPTH),
+            frozen: false,
 
             y: Float::new(),
             y_nibbles: NibbleStream::new(input),
 
-            voices: VoiceGroup::new(8, env),
-            duration: Duration::DELTA,
+            voices: VoiceGroup::new(settings.voices, settings.envelope()),
+            clock: Clock::new(settings.time_signature),
+            next_note: Duration::ZERO,
+            last_note: None,
+        }
+    }
+
+    pub fn table(&self) -> &Wavetable<S> {
+        &self.table
+    }
+
+    /// Get the key currently played in.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Move the key by the given number of semitones.
+    pub fn transpose(&mut self, semitones: i32) {
+        self.key.transpose(semitones);
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Get the time until the next note starts.
+    pub fn until_note(&self) -> Duration {
+        self.next_note - self.clock.now()
+    }
+
+    /// Move forwards in time by `by`, changing key at the start of a bar if
+    /// need be, releasing every note that has ended and starting every note
+    /// due by then.
+    pub fn advance(&mut self, by: Duration) {
+        if self.clock.advance(by) > 0 {
+            let bar = self.clock.bar();
+            if let Some(change) = self.modulation.as_mut().and_then(|m| m.update(bar)) {
+                self.key.change(change);
+                debug!("{change:?} to {} in bar {bar}", self.key);
+            }
+        }
+
+        self.voices.release(self.clock.now());
+        while self.next_note <= self.clock.now() {
+            let (note, provenance) = self.source.next(self.key.base(), self.key.scales());
+            self.voices.add(note, self.next_note);
+            self.next_note += note.duration.max(Duration::TICK);
+            self.last_note = Some((note, provenance));
         }
     }
 
+    /// Get the last note started, and where it came from.
+    pub fn last_note(&self) -> Option<&(Note, Provenance)> {
+        self.last_note.as_ref()
+    }
+
     pub fn slice(&self) -> Vec<u8> {
         self.table.slice(self.y.sample())
     }
 
+    /// Run the next wavetable instruction, once every [`Duration::DELTA`].
+    /// Time is moved forwards separately, see [`Performer::advance`].
     pub fn update(&mut self) {
         self.y.add(0.01 * self.y_nibbles.next_coarse_float());
-        self.table.execute(self.table_nibbles.next_instruction());
-        self.table.increment();
+        let instruction = match &mut self.program {
+            Some(program) => program.next_instruction(),
+            None => self.table_nibbles.next_instruction(),
+        };
 
-        for voice in self.voices.iter_mut() {
-            voice.delta_step();
+        // the instructions keep coming while frozen, so that unfreezing picks
+        // up in step with the text
+        if !self.frozen {
+            self.remember();
+            self.table.execute(instruction);
+            self.table.increment();
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot<S> {
+        self.table.snapshot()
+    }
+
+    /// Restore the wavetable to the given snapshot. This can be undone like
+    /// any instruction.
+    pub fn restore(&mut self, snapshot: &Snapshot<S>) {
+        self.remember();
+        self.table.restore(snapshot);
+    }
+
+    /// Undo the last `steps` changes to the wavetable, or as many as are
+    /// remembered.
+    pub fn undo(&mut self, steps: usize) {
+        let keep = self.history.len().saturating_sub(steps);
+        if let Some(snapshot) = self.history.drain(keep..).next() {
+            self.table.restore(&snapshot);
+        }
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Stop or resume executing wavetable instructions. The melody plays on
+    /// either way.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Remember the current state of the wavetable, so it can be undone.
+    fn remember(&mut self) {
+        if self.history.len() == UNDO_DEPTH {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(self.table.snapshot());
+    }
+
+    /// Save the state of this performer. Sounding notes are not saved.
+    pub fn save(&self, session: &mut Session) {
+        self.table.save(session);
+        session.set_stream("performer.table-nibbles", &self.table_nibbles);
+        if let Some(program) = &self.program {
+            session.set("performer.program-at", program.position());
         }
 
-        if let Some(new) = self.duration.decrement() {
-            self.duration = new;
+        session.set("performer.frozen", self.frozen);
+        session.set("performer.y", self.y.sample());
+        session.set_stream("performer.y-nibbles", &self.y_nibbles);
+        session.set("performer.clock", self.clock.now().ticks());
+        session.set("performer.until-note", self.until_note().ticks());
+        self.key.save(session);
+        if let Some(modulation) = &self.modulation {
+            modulation.save(session);
+        }
+
+        self.source.save(session);
+    }
+
+    /// Restore the state of this performer from a session, leaving it as it
+    /// was if the session is invalid.
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let table = Snapshot::load(session)?;
+        let mut table_nibbles = self.table_nibbles.clone();
+        session.get_stream("performer.table-nibbles", &mut table_nibbles)?;
+        let program_at = match &self.program {
+            Some(_) => Some(session.get("performer.program-at")?),
+            None => None,
+        };
+
+        let frozen = session.get("performer.frozen")?;
+        let y: f64 = session.get("performer.y")?;
+        let mut y_nibbles = self.y_nibbles.clone();
+        session.get_stream("performer.y-nibbles", &mut y_nibbles)?;
+        // sessions from before the clock start over at the top of a bar, and
+        // those from before the finer tick grid count whole deltas
+        let now = if session.has("performer.clock") {
+            Duration::from_ticks(session.get("performer.clock")?)
+        } else {
+            Duration::ZERO
+        };
+        let until_note = if session.has("performer.until-note") {
+            Duration::from_ticks(session.get("performer.until-note")?)
         } else {
-            let note = self.source.next(BASE);
-            self.duration = note.duration;
-            self.voices.add(note);
+            session.get::<usize>("performer.duration")?.max(1) * Duration::DELTA
+        };
+
+        // sessions from before key changes are still in the key of the
+        // settings
+        let mut key = self.key.clone();
+        if session.has("key.base") {
+            key.load(session)?;
+        }
+        let mut modulation = self.modulation.clone();
+        if let Some(modulation) = &mut modulation {
+            if session.has("modulation.nibbles") {
+                modulation.load(session)?;
+            }
         }
+
+        // the source is the last thing that can fail, and leaves itself as it
+        // was when it does
+        self.source.load(session)?;
+
+        self.table.restore(&table);
+        self.history.clear();
+        self.table_nibbles = table_nibbles;
+        if let (Some(program), Some(at)) = (&mut self.program, program_at) {
+            program.set_position(at);
+        }
+
+        self.frozen = frozen;
+        self.y = Float::new();
+        self.y.add(y);
+        self.y_nibbles = y_nibbles;
+        self.clock.set_now(now);
+        self.next_note = now + until_note;
+        self.key = key;
+        self.modulation = modulation;
+        Ok(())
     }
 
     pub fn update_input(&mut self, input: &[u8]) {
         self.source.update_input(input);
         self.table_nibbles = self.table_nibbles.with_new_data(input);
         self.y_nibbles = self.y_nibbles.with_new_data(input);
+        if let Some(modulation) = &mut self.modulation {
+            modulation.update_input(input);
+        }
     }
 
     /// Sample this performer in the given buffer.
-    pub fn sample_in(&mut self, sampler: &Sampler, buffer: &mut [f64]) {
+    pub(crate) fn sample_in(&mut self, sampler: &Sampler, buffer: &mut [f64]) {
         let y = self.y.sample();
         let by = sampler.seconds_per_sample();
 
         for voice in self.voices.iter_mut() {
-            if let Some(pitch) = voice.pitch() {
-                let frequency = pitch.as_frequency();
+            // pitches the tuning leaves out are as silent as rests
+            if let Some(frequency) = voice.pitch().and_then(|pitch| self.tuning.frequency(pitch)) {
+                let band = if self.band_limited {
+                    self.table.band(frequency, sampler.sample_rate())
+                } else {
+                    Band::FULL
+                };
+
+                for buf in buffer.iter_mut() {
+                    // stop right when the voice does, so where the buffer ends
+                    // doesn't affect its phase
+                    if voice.is_done() {
+                        break;
+                    }
 
-                for (i, buf) in buffer.iter_mut().enumerate() {
-                    let sample = sampler.sample(&self.table, frequency, i, y);
+                    let sample = self.table.sample(band, voice.phase(), y);
                     let gain = voice.env();
-                    voice.step(by);
+                    voice.step(frequency, by);
                     *buf += sample * gain;
                 }
             }
         }
     }
 }
+
+/// Get the wavetable a performance of `input` starts from.
+pub fn initial_table<const S: usize>(input: &[u8], settings: &Settings) -> Wavetable<S> {
+    if let Some(audio) = &settings.table {
+        return Wavetable::from_frames(&audio.samples, audio.frame_size());
+    }
+
+    let init = if settings.initializer_from_text {
+        Initializer::from_text(input)
+    } else {
+        settings.initializer
+    };
+
+    Wavetable::new(init)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Performer;
+    use crate::notes::{Duration, KeyMoves};
+    use crate::session::Session;
+    use crate::settings::Settings;
+
+    #[test]
+    fn save_and_load() {
+        let settings = Settings {
+            key_changes: Some(KeyMoves::Mixed),
+            key_every: 1,
+            ..Settings::default()
+        };
+        let mut performer = Performer::<16>::new(b"hello there", &settings);
+        for _ in 0..100 {
+            performer.update();
+            performer.advance(Duration::DELTA);
+        }
+
+        let mut saved = Session::new();
+        performer.save(&mut saved);
+        let saved: Session = saved.to_string().parse().unwrap();
+
+        let mut loaded = Performer::<16>::new(b"hello there", &settings);
+        loaded.load(&saved).unwrap();
+
+        let mut resaved = Session::new();
+        loaded.save(&mut resaved);
+        assert_eq!(saved, resaved);
+
+        let mut smaller = Performer::<8>::new(b"hello there", &settings);
+        assert!(smaller.load(&saved).is_err());
+
+        // a session that turns out invalid halfway through changes nothing
+        let broken: Session = saved
+            .to_string()
+            .replace("markov.duration 2 2 3", "markov.duration 2 2 x")
+            .parse()
+            .unwrap();
+        let mut fresh = Performer::<16>::new(b"hello there", &settings);
+        let mut before = Session::new();
+        fresh.save(&mut before);
+        assert!(fresh.load(&broken).is_err());
+
+        let mut after = Session::new();
+        fresh.save(&mut after);
+        assert_eq!(before, after);
+    }
+}
@@ -1,69 +1,434 @@
-use rtrb::Producer;
-use single_value_channel::Updater;
+use std::collections::VecDeque;
+use std::path::PathBuf;
 
-use crate::aio::BUFFER_SIZE;
+use crate::bytes::NibbleStream;
 use crate::delay::Delay;
-use crate::envelope::AttackDecay;
-use crate::gui::InputPoller;
-use crate::notes::Duration;
+use crate::notes::{Duration, Key, Note};
 use crate::performer::Performer;
 use crate::sampler::Sampler;
+use crate::session::{self, Session};
+use crate::settings::Settings;
+use crate::source::Provenance;
+use crate::wavetable::Snapshot;
 
-pub const BPM: usize = 100;
+/// The most samples rendered at once, and the size of the audio device
+/// buffer when playing live.
+pub const BUFFER_SIZE: usize = 256;
 
-pub fn play(
-    mut audio_channel: Producer<f32>,
+/// The number of slots wavetable snapshots can be saved in.
+pub const SNAPSHOT_SLOTS: usize = 4;
+
+/// A command sent to a running player from the interface.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// Change the tempo to the given number of beats per minute.
+    SetTempo(usize),
+
+    /// Move the key by the given number of semitones.
+    Transpose(i32),
+
+    /// Store the current wavetable in the given slot.
+    SaveSnapshot(usize),
+
+    /// Restore the wavetable stored in the given slot, if any.
+    RecallSnapshot(usize),
+
+    /// Undo the given number of wavetable instructions.
+    Undo(usize),
+
+    /// Stop or resume running wavetable instructions.
+    Freeze(bool),
+
+    /// Save the whole session to the given file.
+    SaveSession(PathBuf),
+}
+
+/// A snapshot of the state of a running player, sent to the interface.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Status {
+    /// The slice of the wavetable currently being played.
+    pub wavetable: Vec<u8>,
+
+    /// The current tempo, in beats per minute.
+    pub bpm: usize,
+
+    /// Whether the wavetable is frozen.
+    pub frozen: bool,
+
+    /// The current bar, counting from zero.
+    pub bar: usize,
+
+    /// The current beat within the bar, counting from zero.
+    pub beat: usize,
+
+    /// The key currently played in.
+    pub key: Key,
+
+    /// The last note started, and where it came from.
+    pub note: Option<(Note, Provenance)>,
+
+    /// The outcome of the last command worth reporting, if any.
+    pub message: String,
+}
+
+/// A change to a running player, which takes effect at a [`Duration::DELTA`]
+/// boundary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// Replace the input text.
+    Input(String),
+
+    /// Run a command.
+    Command(Command),
+}
+
+/// The full audio processing chain: a performer followed by a series of
+/// delays. Independent of any audio device, so it can be driven both in real
+/// time and offline.
+///
+/// A player is deterministic: given the same settings and the same scheduled
+/// events, it renders the same samples, no matter how the output is split up
+/// between calls to [`Player::render`].
+pub struct Player<const S: usize> {
+    performer: Performer<S>,
+    sampler: Sampler,
+    delays: [Delay; 3],
+    snapshots: [Option<Snapshot<S>>; SNAPSHOT_SLOTS],
+
+    input: Vec<u8>,
+    settings: Settings,
+    message: String,
+
+    /// Events waiting for their tick, in order.
+    events: VecDeque<(usize, Event)>,
+
+    tick: usize,
     sample_rate: usize,
-    mut input: InputPoller,
-    wt_send: Updater<Vec<u8>>,
-) {
-    let samples_per_duration = samples_per_duration(sample_rate, BPM);
+    bpm: usize,
+    tempo_from_text: bool,
 
-    let mut sampler = Sampler::new(sample_rate);
+    /// The number of samples in the current [`Duration::DELTA`], and how
+    /// many of them are left.
+    delta_samples: usize,
+    remaining: usize,
 
-    let mut delay1 = Delay::new(2_000, 0.9, 0.8, 0.2);
-    let mut delay2 = Delay::new(15_000, 0.8, 0.7, 0.3);
-    let mut delay3 = Delay::new(40_000, 0.7, 0.6, 0.4);
+    /// How far into the current [`Duration::DELTA`] the performer is.
+    delta_ticks: Duration,
 
-    let data = input.poll().unwrap_or("").as_bytes();
-    let mut performer = Performer::<50>::new(data, AttackDecay::new(0.05, 0.4));
+    /// The fraction of a sample the deltas so far have been rounded down by,
+    /// so that they average out to the exact length.
+    carry: f64,
+}
+
+impl<const S: usize> Player<S> {
+    pub fn new(input: &[u8], sample_rate: usize, settings: &Settings) -> Self {
+        Self {
+            performer: Performer::new(input, settings),
+            sampler: Sampler::new(sample_rate),
+            delays: settings.delays.map(Delay::from_settings),
+            snapshots: Default::default(),
+
+            input: input.to_vec(),
+            settings: settings.clone(),
+            message: String::new(),
+
+            events: VecDeque::new(),
+
+            tick: 0,
+            sample_rate,
+            bpm: initial_tempo(input, settings),
+            tempo_from_text: settings.tempo_from_text,
+
+            delta_samples: 0,
+            remaining: 0,
+            delta_ticks: Duration::ZERO,
+            carry: 0.0,
+        }
+    }
+
+    /// Get the current tempo, in beats per minute.
+    pub fn tempo(&self) -> usize {
+        self.bpm
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            wavetable: self.performer.slice(),
+            bpm: self.bpm,
+            frozen: self.performer.frozen(),
+            bar: self.performer.clock().bar(),
+            beat: self.performer.clock().beat(),
+            key: self.performer.key().clone(),
+            note: self.performer.last_note().cloned(),
+            message: self.message.clone(),
+        }
+    }
+
+    /// Set the message reported in the status.
+    pub fn report(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+    }
+
+    /// Save the input, settings and state of this player.
+    pub fn save(&self) -> Session {
+        let mut session = Session::new();
+        session.set(
+            "text",
+            session::escape(&String::from_utf8_lossy(&self.input)),
+        );
+        session.set("size", S);
+
+        let settings = Settings {
+            bpm: self.bpm,
+            base: self.performer.key().home(),
+            ..self.settings.clone()
+        };
+        settings.save(&mut session);
+        self.performer.save(&mut session);
+
+        session
+    }
+
+    /// Restore the state of this player from a session. The settings stored in
+    /// the session are expected to have been applied already, when creating
+    /// this player.
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        self.performer.load(session)
+    }
 
-    wt_send.update(performer.slice()).unwrap();
+    /// Schedule `event` to take effect at the start of the given tick. Events
+    /// scheduled for the same tick take effect in the order they were
+    /// scheduled in, and events scheduled for a tick that has already started
+    /// take effect at the next one.
+    pub fn schedule(&mut self, tick: usize, event: Event) {
+        let at = self.events.partition_point(|(t, _)| *t <= tick);
+        self.events.insert(at, (tick, event));
+    }
+
+    /// Schedule `event` to take effect as soon as possible, and get the tick
+    /// it will take effect at.
+    pub fn schedule_next(&mut self, event: Event) -> usize {
+        let tick = if self.remaining == 0 {
+            self.tick
+        } else {
+            self.tick + 1
+        };
 
-    let mut buffer = [0.0; BUFFER_SIZE];
+        self.schedule(tick, event);
+        tick
+    }
+
+    /// Fill `out` with the next samples of the performance.
+    pub fn render(&mut self, mut out: &mut [f32]) {
+        let mut buffer = [0.0; BUFFER_SIZE];
+
+        while !out.is_empty() {
+            if self.remaining == 0 {
+                self.start_delta();
+            }
+
+            // never render across a delta boundary or the start of a note, so
+            // how `out` is split up makes no difference
+            let until = self.start_notes();
+            let len = out.len().min(until).min(BUFFER_SIZE);
+            let (chunk, rest) = out.split_at_mut(len);
+            let buffer = &mut buffer[..len];
 
-    loop {
-        for _ in 0..samples_per_duration / BUFFER_SIZE {
             buffer.fill(0.0);
-            performer.sample_in(&sampler, &mut buffer);
-            sampler.step(buffer.len());
+            self.performer.sample_in(&self.sampler, buffer);
 
-            let chunk = loop {
-                if let Ok(chunk) = audio_channel.write_chunk_uninit(BUFFER_SIZE) {
-                    break chunk;
-                }
-            };
-
-            chunk.fill_from_iter(buffer.iter().copied().map(|sample| {
-                let sample = delay1.process(sample);
-                let sample = delay2.process(sample);
-                let sample = delay3.process(sample);
-                sample as f32
-            }));
+            for (out, sample) in chunk.iter_mut().zip(buffer.iter()) {
+                let sample = self
+                    .delays
+                    .iter_mut()
+                    .fold(*sample, |sample, delay| delay.process(sample));
+                *out = sample as f32;
+            }
+
+            self.remaining -= len;
+            if self.remaining == 0 {
+                self.performer.update();
+                self.performer.advance(Duration::DELTA - self.delta_ticks);
+                self.delta_ticks = Duration::ZERO;
+                self.tick += 1;
+            }
+
+            out = rest;
+        }
+    }
+
+    /// Render the performance up to the start of the given tick, counted in
+    /// [`Duration::DELTA`]s like scheduled events, and get the samples. How
+    /// many there are depends on the tempo along the way.
+    pub fn render_until(&mut self, tick: usize) -> Vec<f32> {
+        let mut out = Vec::new();
+        while self.tick < tick {
+            if self.remaining == 0 {
+                self.start_delta();
+            }
+
+            let start = out.len();
+            out.resize(start + self.remaining, 0.0);
+            self.render(&mut out[start..]);
+        }
+
+        out
+    }
+
+    /// Apply the events due at the current tick and work out how many samples
+    /// it lasts.
+    fn start_delta(&mut self) {
+        while self
+            .events
+            .front()
+            .is_some_and(|(tick, _)| *tick <= self.tick)
+        {
+            let (_, event) = self.events.pop_front().unwrap();
+            self.apply(event);
         }
 
-        performer.update();
+        let exact = self.sample_rate as f64 * Duration::DELTA.as_time(self.bpm) + self.carry;
+        let samples = exact.floor();
+        self.carry = exact - samples;
+
+        // always render at least one sample, so very fast tempos still make
+        // progress
+        self.delta_samples = (samples as usize).max(1);
+        self.remaining = self.delta_samples;
+    }
+
+    /// Start the notes due by the current sample, and get the number of
+    /// samples until the next one or the end of the current delta, whichever
+    /// comes first.
+    fn start_notes(&mut self) -> usize {
+        let done = self.delta_samples - self.remaining;
+        let delta = Duration::DELTA.ticks();
+
+        loop {
+            let due = self.delta_ticks + self.performer.until_note();
+            if due >= Duration::DELTA {
+                return self.remaining;
+            }
 
-        if let Some(data) = input.poll() {
-            performer.update_input(data.as_bytes());
+            // notes start on the sample nearest to when they are due
+            let at = (due.ticks() * self.delta_samples + delta / 2) / delta;
+            if at > done {
+                return at - done;
+            }
+
+            self.performer.advance(due - self.delta_ticks);
+            self.delta_ticks = due;
         }
+    }
 
-        wt_send.update(performer.slice()).unwrap();
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Input(input) => self.update_input(input.as_bytes()),
+            Event::Command(command) => self.handle(command),
+        }
+    }
+
+    fn update_input(&mut self, input: &[u8]) {
+        self.input = input.to_vec();
+        self.performer.update_input(input);
+
+        if self.tempo_from_text {
+            self.set_tempo(NibbleStream::new(input).next_tempo());
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: usize) {
+        self.bpm = bpm.max(1);
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::SetTempo(bpm) => self.set_tempo(bpm),
+            Command::Transpose(semitones) => self.performer.transpose(semitones),
+            Command::SaveSnapshot(slot) => {
+                if let Some(snapshot) = self.snapshots.get_mut(slot) {
+                    *snapshot = Some(self.performer.snapshot());
+                }
+            }
+            Command::RecallSnapshot(slot) => {
+                if let Some(Some(snapshot)) = self.snapshots.get(slot) {
+                    self.performer.restore(snapshot);
+                }
+            }
+            Command::Undo(steps) => self.performer.undo(steps),
+            Command::Freeze(frozen) => self.performer.set_frozen(frozen),
+            Command::SaveSession(path) => {
+                self.message = match self.save().write(&path) {
+                    Ok(()) => format!("saved {}", path.display()),
+                    Err(e) => format!("{e:#}"),
+                };
+            }
+        }
+    }
+}
+
+/// Get the tempo a performance of `input` starts at, in beats per minute.
+pub fn initial_tempo(input: &[u8], settings: &Settings) -> usize {
+    if settings.tempo_from_text {
+        NibbleStream::new(input).next_tempo()
+    } else {
+        settings.bpm
     }
 }
 
-/// Get the number of samples
-fn samples_per_duration(sample_rate: usize, bpm: usize) -> usize {
-    let seconds = Duration::DELTA.as_time(bpm);
-    (sample_rate as f64 * seconds) as usize
+impl NibbleStream<2> {
+    /// Get a tempo between 70 and 160 beats per minute.
+    pub fn next_tempo(&mut self) -> usize {
+        let [a, b] = self.next_nibbles();
+        let byte = (a << 4) | b;
+        70 + (90 * byte as usize) / 255
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Event, Player};
+    use crate::settings::Settings;
+
+    #[test]
+    fn render_is_independent_of_chunking() {
+        let settings = Settings::default();
+        let events = [
+            (3, Event::Input("hello there".into())),
+            (3, Event::Command(Command::SetTempo(97))),
+            (20, Event::Command(Command::Transpose(-3))),
+            (40, Event::Command(Command::Freeze(true))),
+            (41, Event::Command(Command::Undo(4))),
+        ];
+
+        let render = |chunk: usize| {
+            let mut player = Player::<32>::new(b"hello", 48_000, &settings);
+            for (tick, event) in events.clone() {
+                player.schedule(tick, event);
+            }
+
+            let mut samples = vec![0.0; 48_000 * 4];
+            for chunk in samples.chunks_mut(chunk) {
+                player.render(chunk);
+            }
+
+            samples
+        };
+
+        let whole = render(usize::MAX);
+        assert!(whole.iter().any(|sample| *sample != 0.0));
+        assert_eq!(whole, render(37));
+        assert_eq!(whole, render(1000));
+    }
+
+    #[test]
+    fn render_until_follows_the_tempo() {
+        let mut player = Player::<32>::new(b"hello", 48_000, &Settings::default());
+        player.schedule(0, Event::Command(Command::SetTempo(60)));
+        player.schedule(8, Event::Command(Command::SetTempo(30)));
+
+        // a thirty-second note lasts 1/8 of a second at 60 bpm
+        assert_eq!(8 * 6_000 + 8 * 12_000, player.render_until(16).len());
+        assert!(player.render_until(16).is_empty());
+    }
 }
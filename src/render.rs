@@ -0,0 +1,153 @@
+//! Offline rendering of a text, without any audio device.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::clock::TimeSignature;
+use crate::notes::Duration;
+use crate::performer::Performer;
+use crate::player::{self, Player};
+use crate::settings::Settings;
+use crate::timeline::Timeline;
+use crate::wav::{self, SampleFormat};
+
+/// The nominal sample rate of exported wavetables. Wavetable synthesizers play
+/// the frames back at whatever pitch they like, so this is only informative.
+const EXPORT_SAMPLE_RATE: usize = 48_000;
+
+/// The length of a rendered piece.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    /// A number of bars.
+    Bars(usize),
+    /// A number of seconds.
+    Seconds(f64),
+}
+
+impl Length {
+    /// Get the number of seconds this length spans at the given tempo and
+    /// time signature.
+    pub fn as_time(&self, bpm: usize, signature: TimeSignature) -> f64 {
+        match self {
+            Self::Bars(bars) => *bars as f64 * signature.bar().as_time(bpm),
+            Self::Seconds(seconds) => *seconds,
+        }
+    }
+
+    /// Get the number of samples this length spans at the given sample rate,
+    /// tempo and time signature.
+    pub fn as_samples(&self, sample_rate: usize, bpm: usize, signature: TimeSignature) -> usize {
+        (self.as_time(bpm, signature) * sample_rate as f64) as usize
+    }
+
+    /// Get the number of [`Duration::DELTA`]s this length spans at the given
+    /// tempo and time signature.
+    pub fn as_deltas(&self, bpm: usize, signature: TimeSignature) -> usize {
+        (self.as_time(bpm, signature) / Duration::DELTA.as_time(bpm)).round() as usize
+    }
+}
+
+impl FromStr for Length {
+    type Err = anyhow::Error;
+
+    /// Parse a length as either a number of bars (`8`) or a number of seconds
+    /// (`12.5s`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.strip_suffix('s') {
+            Some(seconds) => Self::Seconds(seconds.parse()?),
+            None => Self::Bars(s.parse()?),
+        })
+    }
+}
+
+/// Render `input` for the given `length` into a vector of samples. If a
+/// recorded timeline is given, its events are replayed, starting from its
+/// recorded text instead of `input` if it has one.
+pub fn render<const S: usize>(
+    input: &str,
+    length: Length,
+    sample_rate: usize,
+    settings: &Settings,
+    replay: Option<Timeline>,
+) -> anyhow::Result<Vec<f32>> {
+    let (input, events) = match &replay {
+        Some(timeline) => {
+            let (start, events) = timeline.split_start();
+            (start.unwrap_or(input), events)
+        }
+        None => (input, &[][..]),
+    };
+
+    let mut player = Player::<S>::new(input.as_bytes(), sample_rate, settings);
+    if let Some(session) = &settings.session {
+        player.load(session)?;
+    }
+
+    for change in events {
+        player.schedule(change.tick, change.event.clone());
+    }
+
+    // bars are counted in ticks rather than samples, as the tempo may change
+    // along the way
+    let samples = match length {
+        Length::Bars(bars) => player.render_until(bars * settings.time_signature.bar().deltas()),
+        Length::Seconds(_) => {
+            let len = length.as_samples(sample_rate, player.tempo(), settings.time_signature);
+            let mut samples = vec![0.0; len];
+            player.render(&mut samples);
+            samples
+        }
+    };
+
+    Ok(samples)
+}
+
+/// Render `input` for the given `length` and write it to a WAV file at `path`.
+pub fn render_to_file<const S: usize>(
+    input: &str,
+    length: Length,
+    sample_rate: usize,
+    settings: &Settings,
+    format: SampleFormat,
+    path: &Path,
+    replay: Option<Timeline>,
+) -> anyhow::Result<()> {
+    let samples = render::<S>(input, length, sample_rate, settings, replay)?;
+    let file = BufWriter::new(File::create(path)?);
+    wav::write(file, sample_rate, format, &samples)?;
+    Ok(())
+}
+
+/// Run the wavetable program of `input` for the given `length`, and write the
+/// resulting wavetable to a WAV file at `path`, one frame of `frame_size`
+/// samples per row.
+pub fn export_to_file<const S: usize>(
+    input: &str,
+    length: Length,
+    settings: &Settings,
+    frame_size: usize,
+    format: SampleFormat,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let mut performer = Performer::<S>::new(input.as_bytes(), settings);
+    if let Some(session) = &settings.session {
+        performer.load(session)?;
+    }
+
+    let bpm = player::initial_tempo(input.as_bytes(), settings);
+
+    for _ in 0..length.as_deltas(bpm, settings.time_signature) {
+        performer.update();
+        performer.advance(Duration::DELTA);
+    }
+
+    let frames = performer
+        .table()
+        .frames(frame_size, settings.interpolation_x);
+
+    let file = BufWriter::new(File::create(path)?);
+    wav::write_wavetable(file, EXPORT_SAMPLE_RATE, format, frame_size, &frames)?;
+    Ok(())
+}
@@ -1,16 +1,7 @@
-use crate::notes::{Duration, Note, Pitch};
-
-// 0 2  3 5 7  8 10
-// C D Eb F G Ab Bb C
-// C   Eb F G    Bb C
-//
-// C  Eb G  =  0  3  7
-// Eb G  Bb =  3  7 10
-// F  Ab C  =  0  5  8
-// G  Bb D  =  2  7 10
-// Bb D  F  =  2  5 10
-const PENTATONIC_MINOR_CHORDS: [[i32; 3]; 5] =
-    [[0, 3, 7], [3, 7, 10], [0, 5, 8], [2, 7, 10], [2, 5, 10]];
+use anyhow::bail;
+
+use crate::notes::{Duration, Note, Pitch, Scales};
+use crate::session::Session;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Direction {
@@ -47,11 +38,18 @@ impl Sequence {
         }
     }
 
-    /// Generate an arpeggiating sequence, given a `note` in the pentatonic
-    /// minor scale starting at `base`.
-    pub fn new_arp(base: Pitch, note: Pitch, dir: Direction, duration: Duration) -> Option<Self> {
-        let degree = base.pentatonic_minor_scale_number(note)?;
-        let chord = PENTATONIC_MINOR_CHORDS.get(degree).unwrap();
+    /// Generate an arpeggiating sequence, given a `note` in the notes scale
+    /// starting at `base`. The chord is the triad on `note` in the melody
+    /// scale, within the octave above `base`.
+    pub fn new_arp(
+        base: Pitch,
+        scales: &Scales,
+        note: Pitch,
+        dir: Direction,
+        duration: Duration,
+    ) -> Option<Self> {
+        scales.notes.degree(base, note)?;
+        let chord = scales.melody.triad(base, note)?;
 
         Some(Self {
             notes: chord
@@ -91,6 +89,57 @@ impl Sequence {
     }
 }
 
+impl Sequence {
+    /// Save this sequence under the given key prefix.
+    pub fn save(&self, session: &mut Session, prefix: &str) {
+        for note in &self.notes {
+            session.set(&format!("{prefix}.note"), note);
+        }
+
+        let dir = match self.dir.0 {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::PingPong => "pingpong",
+        };
+
+        session.set(&format!("{prefix}.at"), self.at);
+        session.set(&format!("{prefix}.dir"), format!("{dir} {}", self.dir.1));
+    }
+
+    /// Load a sequence saved by [`Sequence::save`].
+    pub fn load(session: &Session, prefix: &str) -> anyhow::Result<Self> {
+        let notes = session
+            .get_all(&format!("{prefix}.note"))
+            .map(str::parse)
+            .collect::<anyhow::Result<Vec<Note>>>()?;
+
+        let at: usize = session.get(&format!("{prefix}.at"))?;
+        if at > 0 && at >= notes.len() {
+            bail!("'{prefix}.at' is past the end of the sequence");
+        }
+
+        let key = format!("{prefix}.dir");
+        let (dir, step) = session.value(&key)?.split_once(' ').unwrap_or_default();
+        let dir = match dir {
+            "up" => Direction::Up,
+            "down" => Direction::Down,
+            "pingpong" => Direction::PingPong,
+            _ => bail!("invalid direction '{dir}' for '{key}'"),
+        };
+
+        let step = match step.parse() {
+            Ok(step @ (-1 | 1)) => step,
+            _ => bail!("invalid step '{step}' for '{key}'"),
+        };
+
+        Ok(Self {
+            notes,
+            at,
+            dir: (dir, step),
+        })
+    }
+}
+
 impl Iterator for Sequence {
     type Item = Note;
 
@@ -0,0 +1,224 @@
+//! Saving and loading of whole performances.
+//!
+//! A session file is line based. Every line holds a key followed by a single
+//! space and its value, and lines starting with `#` are comments. Some keys,
+//! like the rows of the wavetable, appear on several lines, in order.
+
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::bytes::NibbleStream;
+
+const HEADER: &str = "# hannover session";
+
+/// The saved state of a performance, as a list of key value pairs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    entries: Vec<(String, String)>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("could not read '{}'", path.display()))?;
+        source
+            .parse()
+            .with_context(|| format!("in '{}'", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, self.to_string())
+            .with_context(|| format!("could not write '{}'", path.display()))
+    }
+
+    /// Add an entry with the given key. Values must not contain newlines.
+    pub fn set(&mut self, key: &str, value: impl Display) {
+        self.entries.push((key.into(), value.to_string()));
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.get_all(key).next().is_some()
+    }
+
+    /// Get the raw value of the first entry with the given key.
+    pub fn value(&self, key: &str) -> anyhow::Result<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| anyhow!("missing '{key}'"))
+    }
+
+    /// Get the input text that [`Player::save`](crate::Player::save) stored.
+    pub fn text(&self) -> anyhow::Result<String> {
+        self.value("text").map(unescape)
+    }
+
+    /// Parse the value of the first entry with the given key.
+    pub fn get<T>(&self, key: &str) -> anyhow::Result<T>
+    where
+        T: FromStr,
+        T::Err: Into<anyhow::Error>,
+    {
+        let value = self.value(key)?;
+        value
+            .parse()
+            .map_err(Into::into)
+            .with_context(|| format!("invalid value '{value}' for '{key}'"))
+    }
+
+    /// Get the values of every entry with the given key, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Store the position of a nibble stream.
+    pub fn set_stream<const N: usize>(&mut self, key: &str, stream: &NibbleStream<N>) {
+        let (index, total) = stream.position();
+        self.set(key, format!("{index} {total}"));
+    }
+
+    /// Restore the position of a nibble stream.
+    pub fn get_stream<const N: usize>(
+        &self,
+        key: &str,
+        stream: &mut NibbleStream<N>,
+    ) -> anyhow::Result<()> {
+        let [index, total] = fields(self.value(key)?).with_context(|| format!("in '{key}'"))?;
+        stream.set_position(index, total.try_into()?);
+        Ok(())
+    }
+}
+
+/// Parse a value made up of exactly `N` whitespace separated fields.
+pub fn fields<T, const N: usize>(value: &str) -> anyhow::Result<[T; N]>
+where
+    T: FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    let fields = value
+        .split_whitespace()
+        .map(|field| {
+            field
+                .parse()
+                .map_err(Into::into)
+                .with_context(|| format!("invalid field '{field}'"))
+        })
+        .collect::<anyhow::Result<Vec<T>>>()?;
+
+    let len = fields.len();
+    fields
+        .try_into()
+        .map_err(|_| anyhow!("expected {N} fields, found {len}"))
+}
+
+/// Escape backslashes and line breaks, so `text` fits on a single line.
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Undo [`escape`].
+pub fn unescape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                res.push('\n');
+                chars.next();
+            }
+            ('\\', Some('r')) => {
+                res.push('\r');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                res.push('\\');
+                chars.next();
+            }
+            _ => res.push(c),
+        }
+    }
+
+    res
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for (key, value) in &self.entries {
+            writeln!(f, "{key} {value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with(HEADER) {
+            bail!("not a session file");
+        }
+
+        let entries = s
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::bytes::NibbleStream;
+
+    #[test]
+    fn round_trip() {
+        let mut stream = NibbleStream::<3>::new(b"hello");
+        stream.next_nibbles();
+
+        let mut session = Session::new();
+        session.set("text", super::escape("two\r\nlines \\ "));
+        session.set("row", "00ff");
+        session.set("row", "ff00");
+        session.set_stream("stream", &stream);
+
+        let loaded: Session = session.to_string().parse().unwrap();
+        assert_eq!(session, loaded);
+
+        assert_eq!(
+            "two\r\nlines \\ ",
+            super::unescape(loaded.value("text").unwrap())
+        );
+        assert_eq!(
+            vec!["00ff", "ff00"],
+            loaded.get_all("row").collect::<Vec<_>>()
+        );
+
+        let mut restored = NibbleStream::<3>::new(b"hello");
+        loaded.get_stream("stream", &mut restored).unwrap();
+        assert_eq!(stream.next_nibbles(), restored.next_nibbles());
+
+        assert!(loaded.get::<usize>("missing").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::clock::TimeSignature;
+use crate::delay::{self, DelaySettings};
+use crate::envelope::AttackDecay;
+use crate::generator::{GeneratorMap, Registry};
+use crate::notes::{KeyMoves, Pitch, Scales, Tuning};
+use crate::session::{self, Session};
+use crate::wav::Audio;
+use crate::wavetable::asm::Program;
+use crate::wavetable::{Initializer, Interpolation};
+
+/// The tweakable parameters of a performance.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// The tempo, in quarter notes per minute.
+    pub bpm: usize,
+
+    /// How many beats make up a bar.
+    pub time_signature: TimeSignature,
+
+    /// Whether the tempo should be derived from the input text instead.
+    pub tempo_from_text: bool,
+
+    /// The pitch every generated melody is relative to.
+    pub base: Pitch,
+
+    /// The scales generated melodies draw their pitches from.
+    pub scales: Scales,
+
+    /// How pitches map to frequencies.
+    pub tuning: Tuning,
+
+    /// The kinds of key change the text makes, if it changes key at all.
+    pub key_changes: Option<KeyMoves>,
+
+    /// The number of bars between key changes.
+    pub key_every: usize,
+
+    /// The generators notes can come from.
+    pub registry: Registry,
+
+    /// Which generator plays in each of the 16 states of the melody.
+    pub generators: GeneratorMap,
+
+    /// The maximum number of simultaneously sounding notes.
+    pub voices: usize,
+
+    /// The attack time of each note, in seconds.
+    pub attack: f64,
+
+    /// The decay time of each note, in seconds.
+    pub decay: f64,
+
+    /// The delays the performance is sent through, in order.
+    pub delays: [DelaySettings; 3],
+
+    /// Whether to play the wavetable back band limited, which avoids aliasing
+    /// on high notes.
+    pub band_limited: bool,
+
+    /// How to interpolate along the X (wave) axis of the wavetable.
+    pub interpolation_x: Interpolation,
+
+    /// How to interpolate along the Y (morph) axis of the wavetable.
+    pub interpolation_y: Interpolation,
+
+    /// How to fill the wavetable before the performance starts.
+    pub initializer: Initializer,
+
+    /// Whether the initializer should be derived from the input text instead.
+    pub initializer_from_text: bool,
+
+    /// A wavetable to start from, taking precedence over the initializer.
+    pub table: Option<Audio>,
+
+    /// A program to run on the wavetable instead of the instructions from the
+    /// input text.
+    pub program: Option<Program>,
+
+    /// A saved session to resume from.
+    pub session: Option<Session>,
+}
+
+impl Settings {
+    pub fn envelope(&self) -> AttackDecay {
+        AttackDecay::new(self.attack, self.decay)
+    }
+
+    /// Save the settings that shape a running performance. The initial state
+    /// of the wavetable is left out, since the table itself is saved.
+    pub fn save(&self, session: &mut Session) {
+        session.set("bpm", self.bpm);
+        session.set("tempo-from-text", self.tempo_from_text);
+        session.set("time-signature", self.time_signature);
+        session.set("base", self.base);
+        session.set("scale", &self.scales.notes);
+        session.set("melody-scale", &self.scales.melody);
+        self.tuning.save(session);
+        if let Some(moves) = self.key_changes {
+            session.set("key-changes", moves);
+        }
+        session.set("key-every", self.key_every);
+        session.set("generators", &self.generators);
+        session.set("voices", self.voices);
+        session.set("attack", self.attack);
+        session.set("decay", self.decay);
+        session.set("band-limited", self.band_limited);
+        session.set("interp-x", self.interpolation_x);
+        session.set("interp-y", self.interpolation_y);
+
+        for delay in &self.delays {
+            let DelaySettings {
+                samples,
+                feedback,
+                dry,
+                wet,
+            } = delay;
+            session.set("delay", format!("{samples} {feedback} {dry} {wet}"));
+        }
+
+        if let Some(program) = &self.program {
+            let nibbles: String = program.nibbles().iter().map(|n| format!("{n:x}")).collect();
+            session.set("program", nibbles);
+        }
+    }
+
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        self.bpm = session.get("bpm")?;
+        self.tempo_from_text = session.get("tempo-from-text")?;
+        self.time_signature = get_or(session, "time-signature", TimeSignature::COMMON)?;
+        self.base = session.get("base")?;
+
+        // sessions from before scales were configurable use the defaults
+        let default = Scales::default();
+        self.scales = Scales {
+            notes: get_or(session, "scale", default.notes)?,
+            melody: get_or(session, "melody-scale", default.melody)?,
+        };
+
+        self.tuning = if session.has("tuning.mapping") {
+            Tuning::load(session)?
+        } else {
+            Tuning::default()
+        };
+
+        self.key_changes = if session.has("key-changes") {
+            Some(session.get("key-changes")?)
+        } else {
+            None
+        };
+        self.key_every = get_or(session, "key-every", 8)?;
+
+        // sessions from before generators were configurable use the usual mix
+        self.generators = get_or(session, "generators", GeneratorMap::default())?;
+        self.registry.check(&self.generators)?;
+        self.voices = session.get("voices")?;
+        self.attack = session.get("attack")?;
+        self.decay = session.get("decay")?;
+        self.band_limited = session.get("band-limited")?;
+        self.interpolation_x = session.get("interp-x")?;
+        self.interpolation_y = session.get("interp-y")?;
+
+        let delays: Vec<_> = session.get_all("delay").collect();
+        if delays.len() != self.delays.len() {
+            return Err(anyhow!(
+                "expected {} delays, found {}",
+                self.delays.len(),
+                delays.len()
+            ));
+        }
+
+        for (delay, value) in self.delays.iter_mut().zip(delays) {
+            let (samples, rest) = value.split_once(' ').unwrap_or((value, ""));
+            let samples: usize = samples.parse().context("in 'delay'")?;
+            let [feedback, dry, wet]: [f64; 3] = session::fields(rest).context("in 'delay'")?;
+
+            if samples == 0 || samples > delay::MAX_SAMPLES {
+                bail!(
+                    "delay of {samples} samples is out of range (expected 1 to {})",
+                    delay::MAX_SAMPLES
+                );
+            }
+
+            if ![feedback, dry, wet].iter().all(|value| value.is_finite()) {
+                bail!("delay levels must be finite, not '{rest}'");
+            }
+
+            *delay = DelaySettings {
+                samples,
+                feedback,
+                dry,
+                wet,
+            };
+        }
+
+        self.program = if session.has("program") {
+            let hex = session.value("program")?;
+            let program = hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|n| n as u8))
+                .collect::<Option<Vec<_>>>()
+                .and_then(Program::from_nibbles)
+                .ok_or_else(|| anyhow!("invalid program '{hex}'"))?;
+            Some(program)
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+}
+
+/// Parse the value of the given key, or get `default` if it is missing.
+fn get_or<T>(session: &Session, key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    if session.has(key) {
+        session.get(key)
+    } else {
+        Ok(default)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bpm: 100,
+            tempo_from_text: false,
+            time_signature: TimeSignature::COMMON,
+            base: Pitch::A2,
+            scales: Scales::default(),
+            tuning: Tuning::default(),
+            key_changes: None,
+            key_every: 8,
+            registry: Registry::builtin(),
+            generators: GeneratorMap::default(),
+            voices: 8,
+            attack: 0.05,
+            decay: 0.4,
+            delays: [
+                DelaySettings {
+                    samples: 2_000,
+                    feedback: 0.9,
+                    dry: 0.8,
+                    wet: 0.2,
+                },
+                DelaySettings {
+                    samples: 15_000,
+                    feedback: 0.8,
+                    dry: 0.7,
+                    wet: 0.3,
+                },
+                DelaySettings {
+                    samples: 40_000,
+                    feedback: 0.7,
+                    dry: 0.6,
+                    wet: 0.4,
+                },
+            ],
+            band_limited: true,
+            interpolation_x: Interpolation::Linear,
+            interpolation_y: Interpolation::Linear,
+            initializer: Initializer::Sine,
+            initializer_from_text: false,
+            table: None,
+            program: None,
+            session: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use crate::session::Session;
+
+    #[test]
+    fn save_and_load() {
+        let mut saved = Session::new();
+        Settings::default().save(&mut saved);
+        let saved = saved.to_string();
+
+        let mut settings = Settings::default();
+        settings.load(&saved.parse().unwrap()).unwrap();
+        let mut resaved = Session::new();
+        settings.save(&mut resaved);
+        assert_eq!(saved, resaved.to_string());
+
+        // a delay that holds no samples, or levels that are not numbers
+        for (from, to) in [
+            ("delay 2000 ", "delay 0 "),
+            ("delay 2000 ", "delay 99999999999 "),
+            ("delay 2000 0.9", "delay 2000 NaN"),
+        ] {
+            let broken: Session = saved.replacen(from, to, 1).parse().unwrap();
+            assert!(Settings::default().load(&broken).is_err(), "{to}");
+        }
+    }
+}
@@ -1,8 +1,33 @@
+use std::fmt::Display;
+
 use crate::bytes::NibbleStream;
-use crate::markov::MarkovMelody;
-use crate::melody::Melody;
-use crate::notes::{Duration, Note, Pitch};
-use crate::sequence::{Direction, Sequence};
+use crate::generator::{Context, GeneratorMap, Named, Registry, STATES};
+use crate::notes::{Note, Pitch, Scales};
+use crate::session::Session;
+
+/// Which generator a note came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Origin {
+    /// The very first note, which always comes straight from the text.
+    Start,
+    /// The generator with the given name.
+    Generator(String),
+    /// The generator with the given name declined, so the note came straight
+    /// from the text instead.
+    Fallback(String),
+}
+
+/// Where a note came from, to show how the text shapes the melody.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    pub origin: Origin,
+
+    /// The state the source was in, between 0 and 15.
+    pub state: u8,
+
+    /// The number of notes the source produced before this one.
+    pub count: usize,
+}
 
 #[derive(Debug)]
 pub struct NoteSource {
@@ -10,9 +35,9 @@ pub struct NoteSource {
     random_nibbles: NibbleStream<2>,
     state_nibbles: NibbleStream<1>,
 
-    arp: Sequence,
-    chain: MarkovMelody,
-    melody: Melody,
+    generators: Vec<Named>,
+    /// The index of the generator for each state.
+    states: [usize; STATES],
     prev: Option<Note>,
 
     count: usize,
@@ -20,15 +45,25 @@ pub struct NoteSource {
 }
 
 impl NoteSource {
+    /// Create a source playing the built in generators in their usual mix.
     pub fn new(input: &[u8]) -> Self {
+        Self::with_generators(input, &Registry::builtin(), &GeneratorMap::default())
+    }
+
+    /// Create a source playing the generators `map` names.
+    ///
+    /// Panics if `map` names a generator `registry` does not have, see
+    /// [`Registry::check`].
+    pub fn with_generators(input: &[u8], registry: &Registry, map: &GeneratorMap) -> Self {
+        let (generators, states) = registry.create(map, input);
+
         Self {
             note_nibbles: NibbleStream::new(input),
             random_nibbles: NibbleStream::new(input),
             state_nibbles: NibbleStream::new(input),
 
-            arp: Sequence::new(vec![], Direction::Up),
-            chain: MarkovMelody::new(),
-            melody: Melody::new(input),
+            generators,
+            states,
             prev: None,
 
             count: 0,
@@ -36,68 +71,176 @@ impl NoteSource {
         }
     }
 
-    pub fn next(&mut self, base: Pitch) -> Note {
-        let (next, added_to_chain) = match (self.prev, self.state) {
-            (Some(_), 0 | 3 | 7 | 0xa | 0xe) | (None, _) => {
-                (self.note_nibbles.next_note(base), false)
-            }
-
-            (Some(_), 1 | 5 | 9 | 0xc) => match self.arp.next() {
-                Some(note) => (note, false),
-                None => (self.note_nibbles.next_note(base), false),
-            },
-
-            (Some(prev), 2 | 6 | 8 | 0xb | 0xf) => (self.melody.next(base, prev), false),
-
-            (Some(prev), 4 | 0xd) => {
-                let [a, b] = self.random_nibbles.next_nibbles();
-                let random = (a << 4) | b;
+    /// Get the next note in the given scales starting at `base`, along with
+    /// where it came from.
+    pub fn next(&mut self, base: Pitch, scales: &Scales) -> (Note, Provenance) {
+        let mut ctx = Context {
+            base,
+            scales,
+            state: self.state,
+            count: self.count,
+            notes: &mut self.note_nibbles,
+            random: &mut self.random_nibbles,
+        };
 
-                match self.chain.next(prev, random) {
-                    Some(note) => (note, true),
-                    None => (self.note_nibbles.next_note(base), true),
+        // the very first note always comes straight from the text
+        let asked = self
+            .prev
+            .map(|prev| (self.states[self.state as usize], prev));
+        let (next, origin) = match asked {
+            Some((index, prev)) => {
+                let (name, generator) = &mut self.generators[index];
+                match generator.next_note(prev, &mut ctx) {
+                    Some(note) => (note, Origin::Generator(name.clone())),
+                    None => (ctx.raw_note(), Origin::Fallback(name.clone())),
                 }
             }
-
-            (_, 16..=u8::MAX) => unreachable!(),
+            None => (ctx.raw_note(), Origin::Start),
         };
 
-        if !added_to_chain {
-            let [a, b] = self.random_nibbles.next_nibbles();
-            let random = (a << 4) | b;
-            let _ = self.chain.next(next, random);
+        for (index, (_, generator)) in self.generators.iter_mut().enumerate() {
+            let asked = asked.is_some_and(|(asked, _)| asked == index);
+            generator.observe(next, asked, &mut ctx);
         }
 
-        if self.count % 5 == 0 {
-            if let Some(pitch) = next.pitch {
-                let dir = match self.state >> 2 {
-                    0b00 => Direction::Up,
-                    0b01 => Direction::Down,
-                    0b10 => Direction::PingPong,
-                    0b11 => Direction::Up,
-                    _ => unreachable!(),
-                };
-
-                if let Some(arp) = Sequence::new_arp(base, pitch, dir, Duration::EIGHT) {
-                    self.arp = arp;
-                }
-            }
-        }
+        let provenance = Provenance {
+            origin,
+            state: self.state,
+            count: self.count,
+        };
 
-        if self.count % 7 == 0 {
+        if self.count.is_multiple_of(7) {
             self.state = self.state_nibbles.next_nibble();
         }
 
         self.count = self.count.wrapping_add(1);
 
         self.prev = Some(next);
-        next
+        (next, provenance)
+    }
+
+    pub fn save(&self, session: &mut Session) {
+        session.set_stream("source.notes", &self.note_nibbles);
+        session.set_stream("source.random", &self.random_nibbles);
+        session.set_stream("source.state", &self.state_nibbles);
+
+        session.set("source.count", self.count);
+        session.set("source.mode", self.state);
+        if let Some(prev) = self.prev {
+            session.set("source.prev", prev);
+        }
+
+        for (_, generator) in &self.generators {
+            generator.save(session);
+        }
+    }
+
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let (mut note_nibbles, mut random_nibbles, mut state_nibbles) = (
+            self.note_nibbles.clone(),
+            self.random_nibbles.clone(),
+            self.state_nibbles.clone(),
+        );
+        session.get_stream("source.notes", &mut note_nibbles)?;
+        session.get_stream("source.random", &mut random_nibbles)?;
+        session.get_stream("source.state", &mut state_nibbles)?;
+
+        let count = session.get("source.count")?;
+        let state = session.get::<u8>("source.mode")? & 0xf;
+        let prev = if session.has("source.prev") {
+            Some(session.get("source.prev")?)
+        } else {
+            None
+        };
+
+        // generators load in place, so put those already loaded back the way
+        // they were if a later one fails
+        let mut before = Session::new();
+        for (_, generator) in &self.generators {
+            generator.save(&mut before);
+        }
+
+        for index in 0..self.generators.len() {
+            if let Err(err) = self.generators[index].1.load(session) {
+                for (_, generator) in &mut self.generators[..index] {
+                    generator
+                        .load(&before)
+                        .expect("generators load what they saved");
+                }
+
+                return Err(err);
+            }
+        }
+
+        self.note_nibbles = note_nibbles;
+        self.random_nibbles = random_nibbles;
+        self.state_nibbles = state_nibbles;
+        self.count = count;
+        self.state = state;
+        self.prev = prev;
+        Ok(())
     }
 
     pub fn update_input(&mut self, input: &[u8]) {
         self.note_nibbles = self.note_nibbles.with_new_data(input);
         self.random_nibbles = self.random_nibbles.with_new_data(input);
         self.state_nibbles = self.state_nibbles.with_new_data(input);
-        self.melody.update_input(input);
+        for (_, generator) in &mut self.generators {
+            generator.update_input(input);
+        }
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Generator(name) => write!(f, "{name}"),
+            Self::Fallback(name) => write!(f, "{name}, fell back to raw"),
+        }
+    }
+}
+
+impl Display for Provenance {
+    /// Write this as e.g. `arp  state 5  #123`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}  state {}  #{}", self.origin, self.state, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NoteSource, Origin};
+    use crate::generator::{Context, NoteGenerator, Registry};
+    use crate::notes::{Note, Pitch, Scales};
+
+    #[derive(Debug)]
+    struct Silent;
+
+    impl NoteGenerator for Silent {
+        fn next_note(&mut self, _prev: Note, _ctx: &mut Context) -> Option<Note> {
+            None
+        }
+    }
+
+    #[test]
+    fn provenance() {
+        let mut registry = Registry::builtin();
+        registry.register("silent", |_| Box::new(Silent));
+
+        let map = "silent,melody".parse().unwrap();
+        let mut source = NoteSource::with_generators(b"hello", &registry, &map);
+
+        let (_, first) = source.next(Pitch::A2, &Scales::default());
+        assert_eq!(Origin::Start, first.origin);
+        assert_eq!(0, first.count);
+
+        let (_, second) = source.next(Pitch::A2, &Scales::default());
+        let expected = match second.state % 2 {
+            0 => Origin::Fallback("silent".into()),
+            _ => Origin::Generator("melody".into()),
+        };
+        assert_eq!(expected, second.origin);
+        assert_eq!(1, second.count);
     }
 }
@@ -0,0 +1,196 @@
+//! Recording and replaying the events of a performance.
+//!
+//! A timeline file holds one event per line: the [`Duration::DELTA`] tick the
+//! event took effect at, followed by the event itself. That is one of
+//! `text <text>`, `tempo <bpm>`, `transpose <semitones>`, `save <slot>`,
+//! `recall <slot>`, `undo <steps>` or `freeze <true|false>`. Saving the
+//! session is not recorded.
+//!
+//! [`Duration::DELTA`]: crate::notes::Duration::DELTA
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::player::{Command, Event};
+use crate::session;
+
+const HEADER: &str = "# hannover timeline";
+
+/// A single event of a performance.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Change {
+    /// The number of [`crate::notes::Duration::DELTA`]s since the start of
+    /// the performance.
+    pub tick: usize,
+    pub event: Event,
+}
+
+/// The events of a performance, in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Timeline {
+    changes: Vec<Change>,
+}
+
+impl Timeline {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("could not read '{}'", path.display()))?;
+        source
+            .parse()
+            .with_context(|| format!("in '{}'", path.display()))
+    }
+
+    /// Get the tick of the last change.
+    pub fn end(&self) -> usize {
+        self.changes.last().map_or(0, |change| change.tick)
+    }
+
+    /// Get the text the performance started with, if it was recorded, along
+    /// with the changes after it.
+    pub fn split_start(&self) -> (Option<&str>, &[Change]) {
+        match self.changes.split_first() {
+            Some((
+                Change {
+                    tick: 0,
+                    event: Event::Input(text),
+                },
+                rest,
+            )) => (Some(text), rest),
+            _ => (None, &self.changes),
+        }
+    }
+}
+
+impl FromStr for Timeline {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with(HEADER) {
+            bail!("not a timeline file");
+        }
+
+        let mut changes: Vec<Change> = vec![];
+        for (line, source) in s.lines().enumerate() {
+            if source.is_empty() || source.starts_with('#') {
+                continue;
+            }
+
+            let (tick, event) = source.split_once(' ').unwrap_or((source, ""));
+            let tick = tick
+                .parse()
+                .map_err(|_| anyhow!("line {}: invalid tick '{tick}'", line + 1))?;
+            let event = parse_event(event).with_context(|| format!("line {}", line + 1))?;
+
+            if changes.last().is_some_and(|last| last.tick > tick) {
+                bail!("line {}: changes are out of order", line + 1);
+            }
+
+            changes.push(Change { tick, event });
+        }
+
+        Ok(Self { changes })
+    }
+}
+
+fn parse_event(source: &str) -> anyhow::Result<Event> {
+    let (kind, value) = source.split_once(' ').unwrap_or((source, ""));
+    let command = match kind {
+        "text" => return Ok(Event::Input(session::unescape(value))),
+        "tempo" => Command::SetTempo(value.parse()?),
+        "transpose" => Command::Transpose(value.parse()?),
+        "save" => Command::SaveSnapshot(value.parse()?),
+        "recall" => Command::RecallSnapshot(value.parse()?),
+        "undo" => Command::Undo(value.parse()?),
+        "freeze" => Command::Freeze(value.parse()?),
+        _ => bail!("unknown event '{kind}'"),
+    };
+
+    Ok(Event::Command(command))
+}
+
+/// Writes events to a timeline file as they happen, so nothing is lost when
+/// a live performance is cut short.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("could not create '{}'", path.display()))?;
+
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{HEADER}")?;
+
+        Ok(Self { out })
+    }
+
+    /// Record `event` as taking effect at the given tick. Events which don't
+    /// change the performance, like saving the session, are skipped.
+    pub fn record(&mut self, tick: usize, event: &Event) -> io::Result<()> {
+        let event = match event {
+            Event::Input(text) => format!("text {}", session::escape(text)),
+            Event::Command(command) => match command {
+                Command::SetTempo(bpm) => format!("tempo {bpm}"),
+                Command::Transpose(semitones) => format!("transpose {semitones}"),
+                Command::SaveSnapshot(slot) => format!("save {slot}"),
+                Command::RecallSnapshot(slot) => format!("recall {slot}"),
+                Command::Undo(steps) => format!("undo {steps}"),
+                Command::Freeze(frozen) => format!("freeze {frozen}"),
+                Command::SaveSession(_) => return Ok(()),
+            },
+        };
+
+        writeln!(self.out, "{tick} {event}")?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, Timeline};
+    use crate::player::{Command, Event};
+
+    #[test]
+    fn parse() {
+        let timeline: Timeline =
+            "# hannover timeline\n0 text \n12 text hello\n12 freeze true\n20 text a\\nb\n"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            &[
+                Change {
+                    tick: 0,
+                    event: Event::Input(String::new())
+                },
+                Change {
+                    tick: 12,
+                    event: Event::Input("hello".into())
+                },
+                Change {
+                    tick: 12,
+                    event: Event::Command(Command::Freeze(true))
+                },
+                Change {
+                    tick: 20,
+                    event: Event::Input("a\nb".into())
+                },
+            ],
+            &timeline.changes[..]
+        );
+
+        let (start, rest) = timeline.split_start();
+        assert_eq!(Some(""), start);
+        assert_eq!(3, rest.len());
+
+        assert!("# hannover timeline\n5 text a\n4 text b"
+            .parse::<Timeline>()
+            .is_err());
+        assert!("# hannover timeline\n5 jump 3".parse::<Timeline>().is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::envelope::AttackDecay;
-use crate::notes::Note;
+use crate::notes::{Duration, Note};
 
 use super::Voice;
 
@@ -17,16 +17,23 @@ impl VoiceGroup {
         }
     }
 
-    /// Add a note to one of the voices in this group.
-    pub fn add(&mut self, note: Note) {
+    /// Add a note starting at `start` to one of the voices in this group.
+    pub fn add(&mut self, note: Note, start: Duration) {
         if let Some(voice) = self.voices.iter_mut().find(|voice| voice.is_done()) {
-            voice.replace(note);
+            voice.replace(note, start);
         } else if !self.voices.is_empty() {
-            self.voices.get_mut(self.at).unwrap().replace(note);
+            self.voices.get_mut(self.at).unwrap().replace(note, start);
             self.at = self.at.wrapping_add(1) % self.voices.len();
         }
     }
 
+    /// Release the voices whose notes have ended by `now`.
+    pub fn release(&mut self, now: Duration) {
+        for voice in &mut self.voices {
+            voice.release(now);
+        }
+    }
+
     /// Get an iterator over all the non-silent voices in this group.
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Voice> {
         self.voices
@@ -34,3 +41,39 @@ impl VoiceGroup {
             .filter_map(|voice| (!voice.is_done()).then_some(voice))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::VoiceGroup;
+    use crate::envelope::AttackDecay;
+    use crate::notes::{Duration, Note, Pitch};
+
+    /// Step every voice a tenth of a second, and count those still playing.
+    fn sounding(voices: &mut VoiceGroup) -> usize {
+        for voice in voices.iter_mut() {
+            voice.step(110.0, 0.1);
+        }
+
+        voices
+            .iter_mut()
+            .filter(|voice| voice.pitch().is_some())
+            .count()
+    }
+
+    #[test]
+    fn notes_stop_at_their_duration() {
+        // a decay long enough that only the end of the note can silence it
+        let mut voices = VoiceGroup::new(2, AttackDecay::new(0.01, 60.0));
+        let note = Note {
+            pitch: Some(Pitch::A2),
+            duration: Duration::QUARTER,
+        };
+        voices.add(note, Duration::QUARTER);
+
+        voices.release(2 * Duration::QUARTER - Duration::TICK);
+        assert_eq!(1, sounding(&mut voices));
+
+        voices.release(2 * Duration::QUARTER);
+        assert_eq!(0, sounding(&mut voices));
+    }
+}
@@ -3,17 +3,28 @@ mod group;
 pub use group::VoiceGroup;
 
 use crate::envelope::AttackDecay;
-use crate::notes::{Note, Pitch};
+use crate::notes::{Duration, Note, Pitch};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Voice {
     note: Option<Note>,
     env: AttackDecay,
+
+    /// When the current note ends, in the time of the performance.
+    end: Duration,
+
+    /// The position within the current wave cycle, in the range `[0, 1)`.
+    phase: f64,
 }
 
 impl Voice {
     pub fn new(env: AttackDecay) -> Self {
-        Self { note: None, env }
+        Self {
+            note: None,
+            env,
+            end: Duration::ZERO,
+            phase: 0.0,
+        }
     }
 
     /// Get the current pitch for this voice, if any.
@@ -26,27 +37,34 @@ impl Voice {
         self.env.value()
     }
 
-    /// Step the envelope forward `by` seconds.
-    pub fn step(&mut self, by: f64) {
-        self.env.step(by);
+    /// Get the position within the current wave cycle, in the range `[0, 1)`.
+    pub fn phase(&self) -> f64 {
+        self.phase
     }
 
-    /// Move one [`Duration::DELTA`] forwards in time. Calls `f` to provide a
-    /// new note if the current one is done.
-    pub fn delta_step(&mut self) {
-        let Some(note) = &mut self.note else { return; };
-
-        if let Some(duration) = note.duration.decrement() {
-            note.duration = duration;
-        }
+    /// Step the envelope forward `by` seconds, and the phase forward by as
+    /// many cycles of the given `frequency`.
+    pub fn step(&mut self, frequency: f64, by: f64) {
+        self.env.step(by);
+        self.phase = (self.phase + frequency * by).rem_euclid(1.0);
     }
 
     pub fn is_done(&self) -> bool {
         self.env.is_done()
     }
 
-    pub fn replace(&mut self, note: Note) {
+    /// Start playing a new note at `start`. The phase is left as is, so the
+    /// wave stays continuous.
+    pub fn replace(&mut self, note: Note, start: Duration) {
         self.note = Some(note);
+        self.end = start + note.duration;
         self.env.reset();
     }
+
+    /// Release the current note if it has ended by `now`.
+    pub fn release(&mut self, now: Duration) {
+        if self.end <= now {
+            self.env.release();
+        }
+    }
 }
@@ -0,0 +1,308 @@
+//! Reading and writing of mono RIFF WAVE files.
+
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// The format of the samples stored in a WAV file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SampleFormat {
+    /// 16-bit signed integer PCM.
+    Int16,
+    /// 24-bit signed integer PCM.
+    Int24,
+    /// 32-bit IEEE float.
+    Float32,
+}
+
+impl SampleFormat {
+    fn bits(&self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+
+    fn tag(&self) -> u16 {
+        match self {
+            Self::Int16 | Self::Int24 => 1,
+            Self::Float32 => 3,
+        }
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i16" => Ok(Self::Int16),
+            "i24" => Ok(Self::Int24),
+            "f32" => Ok(Self::Float32),
+            _ => Err(anyhow!(
+                "unknown sample format '{s}' (expected i16, i24 or f32)"
+            )),
+        }
+    }
+}
+
+/// The frame size wavetable files without a `clm ` chunk are assumed to use,
+/// if their length is a multiple of it.
+pub const DEFAULT_FRAME_SIZE: usize = 2048;
+
+/// The contents of a WAV file, mixed down to mono.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub sample_rate: usize,
+    pub samples: Vec<f32>,
+
+    /// The size of each single cycle frame, if the file is marked as a
+    /// wavetable.
+    pub frame_size: Option<usize>,
+}
+
+impl Audio {
+    /// Guess the size of the single cycle frames in this file. This is the
+    /// size given in its `clm ` chunk if it has one, otherwise
+    /// [`DEFAULT_FRAME_SIZE`] if the length is a multiple of it, and otherwise
+    /// the whole file is taken to be a single cycle.
+    pub fn frame_size(&self) -> usize {
+        match self.frame_size {
+            Some(size) if size > 0 && size <= self.samples.len() => size,
+            _ if self.samples.len().is_multiple_of(DEFAULT_FRAME_SIZE) => DEFAULT_FRAME_SIZE,
+            _ => self.samples.len(),
+        }
+    }
+}
+
+/// Read a WAV file in any of the integer PCM or float formats, mixing all its
+/// channels down to mono.
+pub fn read<R: Read>(mut input: R) -> io::Result<Audio> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut header = [0; 12];
+    input.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(invalid("not a RIFF WAVE file"));
+    }
+
+    let mut format = None;
+    let mut frame_size = None;
+
+    loop {
+        let mut chunk_header = [0; 8];
+        input.read_exact(&mut chunk_header)?;
+        let id = &chunk_header[0..4];
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as usize;
+
+        // Don't trust the size enough to allocate it up front, as it may be
+        // far larger than the file.
+        let mut data = vec![];
+        input.by_ref().take(size as u64).read_to_end(&mut data)?;
+        if data.len() < size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "chunk extends past the end of the file",
+            ));
+        }
+
+        // chunks are padded to an even size
+        if size % 2 == 1 {
+            input.read_exact(&mut [0])?;
+        }
+
+        match id {
+            b"fmt " => {
+                if data.len() < 16 {
+                    return Err(invalid("format chunk too short"));
+                }
+
+                let word = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+                let mut tag = word(0);
+                // WAVE_FORMAT_EXTENSIBLE keeps the actual tag in its subformat
+                if tag == 0xfffe && data.len() >= 26 {
+                    tag = word(24);
+                }
+
+                let channels = word(2) as usize;
+                let sample_rate = u32::from_le_bytes(data[4..8].try_into().unwrap());
+                let bits = word(14);
+
+                format = Some((tag, channels.max(1), sample_rate as usize, bits));
+            }
+
+            b"clm " => {
+                // e.g. "<!>2048 00000000 wavetable (vendor)"
+                frame_size = String::from_utf8_lossy(&data)
+                    .strip_prefix("<!>")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|size| size.parse().ok());
+            }
+
+            b"data" => {
+                let (tag, channels, sample_rate, bits) =
+                    format.ok_or_else(|| invalid("data chunk before format chunk"))?;
+
+                let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+                    (1, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+                    (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+                    (1, 24) => {
+                        |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0
+                    }
+                    (1, 32) => {
+                        |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+                    }
+                    (3, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                    (3, 64) => |b| f64::from_le_bytes(b[0..8].try_into().unwrap()) as f32,
+                    _ => return Err(invalid("unsupported sample format")),
+                };
+
+                let width = bits as usize / 8;
+                let samples = data
+                    .chunks_exact(width * channels)
+                    .map(|frame| {
+                        let sum: f32 = frame.chunks_exact(width).map(decode).sum();
+                        sum / channels as f32
+                    })
+                    .collect();
+
+                return Ok(Audio {
+                    sample_rate,
+                    samples,
+                    frame_size,
+                });
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// An additional chunk to store in a WAV file.
+pub struct Chunk<'a> {
+    pub id: [u8; 4],
+    pub data: &'a [u8],
+}
+
+/// Write the given `samples` as a mono WAV file. Samples are expected to lie in
+/// the range `[-1, 1]`, and are clipped to this range for the integer formats.
+pub fn write<W: Write>(
+    out: W,
+    sample_rate: usize,
+    format: SampleFormat,
+    samples: &[f32],
+) -> io::Result<()> {
+    write_with_chunks(out, sample_rate, format, samples, &[])
+}
+
+/// Write the given `samples` as a wavetable of single cycle frames of
+/// `frame_size` samples each, marked with a `clm ` chunk as most wavetable
+/// synthesizers expect.
+pub fn write_wavetable<W: Write>(
+    out: W,
+    sample_rate: usize,
+    format: SampleFormat,
+    frame_size: usize,
+    samples: &[f32],
+) -> io::Result<()> {
+    let marker = format!("<!>{frame_size} 00000000 wavetable (hannover)");
+    let chunk = Chunk {
+        id: *b"clm ",
+        data: marker.as_bytes(),
+    };
+
+    write_with_chunks(out, sample_rate, format, samples, &[chunk])
+}
+
+/// Write the given `samples` as a mono WAV file, with the given chunks
+/// between the format and data chunks.
+pub fn write_with_chunks<W: Write>(
+    mut out: W,
+    sample_rate: usize,
+    format: SampleFormat,
+    samples: &[f32],
+    chunks: &[Chunk],
+) -> io::Result<()> {
+    let bytes_per_sample = format.bits() as u32 / 8;
+    let data_size = bytes_per_sample * samples.len() as u32;
+
+    // chunks are padded to an even number of bytes
+    let padded = |len: usize| (len + len % 2) as u32;
+    let chunks_size: u32 = chunks
+        .iter()
+        .map(|chunk| 8 + padded(chunk.data.len()))
+        .sum();
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(4 + (8 + 16) + chunks_size + (8 + data_size)).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&format.tag().to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&(sample_rate as u32).to_le_bytes())?;
+    out.write_all(&(sample_rate as u32 * bytes_per_sample).to_le_bytes())?;
+    out.write_all(&(bytes_per_sample as u16).to_le_bytes())?;
+    out.write_all(&format.bits().to_le_bytes())?;
+
+    for chunk in chunks {
+        out.write_all(&chunk.id)?;
+        out.write_all(&(chunk.data.len() as u32).to_le_bytes())?;
+        out.write_all(chunk.data)?;
+        if chunk.data.len() % 2 != 0 {
+            out.write_all(&[0])?;
+        }
+    }
+
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())?;
+
+    for sample in samples.iter().copied() {
+        match format {
+            SampleFormat::Int16 => {
+                let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                out.write_all(&value.to_le_bytes())?;
+            }
+
+            SampleFormat::Int24 => {
+                let value = (sample.clamp(-1.0, 1.0) * 0x7f_ffff as f32) as i32;
+                out.write_all(&value.to_le_bytes()[..3])?;
+            }
+
+            SampleFormat::Float32 => out.write_all(&sample.to_le_bytes())?,
+        }
+    }
+
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SampleFormat;
+
+    #[test]
+    fn wavetable_round_trip() {
+        let samples: Vec<f32> = (0..8).map(|i| i as f32 / 8.0 - 0.5).collect();
+
+        let mut file = vec![];
+        super::write_wavetable(&mut file, 44_100, SampleFormat::Float32, 4, &samples).unwrap();
+
+        let audio = super::read(file.as_slice()).unwrap();
+        assert_eq!(44_100, audio.sample_rate);
+        assert_eq!(samples, audio.samples);
+        assert_eq!(4, audio.frame_size());
+    }
+
+    #[test]
+    fn read_truncated_chunk() {
+        let mut file = b"RIFF\0\0\0\0WAVEdata".to_vec();
+        file.extend(u32::MAX.to_le_bytes());
+        file.extend([0; 16]);
+
+        let error = super::read(file.as_slice()).unwrap_err();
+        assert_eq!(std::io::ErrorKind::UnexpectedEof, error.kind());
+    }
+}
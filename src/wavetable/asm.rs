@@ -0,0 +1,286 @@
+//! A textual assembly language for the wavetable virtual machine.
+//!
+//! A program consists of one instruction per line, written as its mnemonic
+//! followed by its operands, and everything following a `;` is a comment. See
+//! `docs/wtvm.txt` for the full list of mnemonics.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::Instruction;
+use crate::data::PRIMES;
+
+/// An error in a single line of an assembly program.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InstructionError {
+    UnknownMnemonic(String),
+    MissingOperand,
+    UnexpectedOperand(String),
+    /// The operand is not one of the primes the instruction set can encode.
+    NotAPrime(String),
+    /// The two operands of `mulcur` must be the primes of some nibble and of
+    /// 15 minus that nibble.
+    MismatchedPrimes(usize, usize),
+}
+
+/// An error in an assembly program.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AsmError {
+    /// The line the error occurred on, starting at 1.
+    pub line: usize,
+    pub error: InstructionError,
+}
+
+/// An assembled program, which loops forever.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Program {
+    nibbles: Vec<u8>,
+    at: usize,
+}
+
+impl Program {
+    /// Get the nibble encoding of this program.
+    pub fn nibbles(&self) -> &[u8] {
+        &self.nibbles
+    }
+
+    /// Get the next nibble of this program, wrapping around at the end. An
+    /// empty program produces all zeroes.
+    pub fn next_nibble(&mut self) -> u8 {
+        let Some(nibble) = self.nibbles.get(self.at).copied() else {
+            return 0;
+        };
+
+        self.at = (self.at + 1) % self.nibbles.len();
+        nibble
+    }
+
+    /// Create a program from its nibble encoding. Returns `None` if any of the
+    /// values is not a nibble.
+    pub fn from_nibbles(nibbles: Vec<u8>) -> Option<Self> {
+        nibbles
+            .iter()
+            .all(|nibble| *nibble < 0x10)
+            .then_some(Self { nibbles, at: 0 })
+    }
+
+    /// Get the index of the next nibble of this program.
+    pub fn position(&self) -> usize {
+        self.at
+    }
+
+    pub fn set_position(&mut self, at: usize) {
+        self.at = if self.nibbles.is_empty() {
+            0
+        } else {
+            at % self.nibbles.len()
+        };
+    }
+
+    pub fn next_instruction(&mut self) -> Instruction {
+        Instruction::decode(|| self.next_nibble())
+    }
+}
+
+impl FromStr for Program {
+    type Err = AsmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut nibbles = vec![];
+
+        for (line, source) in s.lines().enumerate() {
+            let source = source.split(';').next().unwrap_or("").trim();
+            if source.is_empty() {
+                continue;
+            }
+
+            let instruction: Instruction = source.parse().map_err(|error| AsmError {
+                line: line + 1,
+                error,
+            })?;
+
+            nibbles.extend(instruction.encode());
+        }
+
+        Ok(Self { nibbles, at: 0 })
+    }
+}
+
+impl Instruction {
+    /// Encode this instruction as the nibbles [`Instruction::decode`] reads.
+    ///
+    /// Panics if an operand is not one of the first sixteen primes, which is
+    /// never the case for decoded or parsed instructions.
+    pub fn encode(&self) -> Vec<u8> {
+        let prime = |p: usize| {
+            PRIMES
+                .iter()
+                .position(|q| *q == p)
+                .expect("operand is an encodable prime") as u8
+        };
+
+        match self {
+            Self::Noop => vec![0x0],
+            Self::MultiplyCursor(x, _) => vec![0x1, prime(*x)],
+            Self::VoronoiCursor => vec![0x2],
+            Self::MultiplyData(by) => vec![0x3, prime(*by)],
+            Self::Slant => vec![0x4],
+            Self::Smooth => vec![0x5],
+            Self::Substitution => vec![0x6],
+            Self::SignedDataMove => vec![0x7],
+            Self::MoveDiagonal(start) => vec![0x8, prime(*start)],
+            Self::Transpose => vec![0x9],
+            Self::RemoveOldest => vec![0xa],
+            Self::Gaussian => vec![0xb],
+            Self::RotateRow(by) => vec![0xc, prime(*by)],
+            Self::MirrorRow => vec![0xd],
+            Self::ReseedSine => vec![0xe],
+            Self::XorRows => vec![0xf],
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = InstructionError;
+
+    /// Parse a single instruction, in the form printed by its `Display`
+    /// implementation. The second operand of `mulcur` may be left out.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let mnemonic = words.next().unwrap_or("");
+
+        let mut operand = || -> Result<usize, InstructionError> {
+            let word = words.next().ok_or(InstructionError::MissingOperand)?;
+            word.parse()
+                .ok()
+                .filter(|p| PRIMES.contains(p))
+                .ok_or_else(|| InstructionError::NotAPrime(word.into()))
+        };
+
+        let instruction = match mnemonic {
+            "nop" => Self::Noop,
+            "mulcur" => {
+                let x = operand()?;
+                let index = PRIMES.iter().position(|p| *p == x).unwrap();
+                let y = PRIMES[0xf - index];
+
+                match operand() {
+                    Ok(given) if given != y => {
+                        return Err(InstructionError::MismatchedPrimes(x, given))
+                    }
+                    Ok(_) | Err(InstructionError::MissingOperand) => {}
+                    Err(e) => return Err(e),
+                }
+
+                Self::MultiplyCursor(x, y)
+            }
+            "voronoi" => Self::VoronoiCursor,
+            "muldata" => Self::MultiplyData(operand()?),
+            "slant" => Self::Slant,
+            "smooth" => Self::Smooth,
+            "sbox" => Self::Substitution,
+            "datamove" => Self::SignedDataMove,
+            "diag" => Self::MoveDiagonal(operand()?),
+            "transpose" => Self::Transpose,
+            "dropcur" => Self::RemoveOldest,
+            "gauss" => Self::Gaussian,
+            "rotrow" => Self::RotateRow(operand()?),
+            "mirror" => Self::MirrorRow,
+            "reseed" => Self::ReseedSine,
+            "xorrow" => Self::XorRows,
+            _ => return Err(InstructionError::UnknownMnemonic(mnemonic.into())),
+        };
+
+        match words.next() {
+            Some(word) => Err(InstructionError::UnexpectedOperand(word.into())),
+            None => Ok(instruction),
+        }
+    }
+}
+
+impl Display for InstructionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic '{mnemonic}'"),
+            Self::MissingOperand => write!(f, "missing operand"),
+            Self::UnexpectedOperand(word) => write!(f, "unexpected operand '{word}'"),
+            Self::NotAPrime(word) => {
+                write!(f, "'{word}' is not one of the primes {PRIMES:?}")
+            }
+            Self::MismatchedPrimes(x, y) => write!(
+                f,
+                "'mulcur {x}' implies a second operand of {}, not {y}",
+                PRIMES[0xf - PRIMES.iter().position(|p| p == x).unwrap()]
+            ),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsmError, InstructionError, Program};
+    use crate::wavetable::Instruction;
+
+    #[test]
+    fn assemble_and_decode() {
+        let source = "
+            ; a small program
+            slant
+            mulcur 3        ; the y prime is implied
+            muldata 41
+            rotrow 2
+            mulcur 53 2
+        ";
+
+        let mut program: Program = source.parse().unwrap();
+        assert_eq!(
+            &[0x4, 0x1, 0x1, 0x3, 0xc, 0xc, 0x0, 0x1, 0xf],
+            program.nibbles()
+        );
+
+        let expected = [
+            Instruction::Slant,
+            Instruction::MultiplyCursor(3, 47),
+            Instruction::MultiplyData(41),
+            Instruction::RotateRow(2),
+            Instruction::MultiplyCursor(53, 2),
+            Instruction::Slant,
+        ];
+
+        for expected in expected {
+            let instruction = program.next_instruction();
+            assert_eq!(expected, instruction);
+            assert_eq!(Ok(instruction), instruction.to_string().parse());
+        }
+    }
+
+    #[test]
+    fn report_errors() {
+        let error = "slant\nmuldata 4".parse::<Program>().unwrap_err();
+        assert_eq!(
+            AsmError {
+                line: 2,
+                error: InstructionError::NotAPrime("4".into())
+            },
+            error
+        );
+
+        assert_eq!(
+            Err(InstructionError::MismatchedPrimes(3, 5)),
+            "mulcur 3 5".parse::<Instruction>()
+        );
+
+        assert_eq!(
+            Err(InstructionError::UnexpectedOperand("7".into())),
+            "gauss 7".parse::<Instruction>()
+        );
+    }
+}
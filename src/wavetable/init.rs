@@ -0,0 +1,150 @@
+use std::f64::consts::TAU;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::bytes::NibbleStream;
+use crate::data::RIJNDAEL_SBOX;
+
+/// The number of harmonics [`Initializer::Additive`] sums.
+const ADDITIVE_HARMONICS: usize = 16;
+
+/// The largest modulation index [`Initializer::Fm`] reaches, on its last row.
+const FM_MAX_INDEX: f64 = 4.0;
+
+/// A way of filling a fresh wavetable.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Initializer {
+    /// A sine wave whose phase shifts along the Y axis.
+    #[default]
+    Sine,
+    /// A rising sawtooth wave.
+    Saw,
+    /// A pulse wave whose width grows along the Y axis.
+    Square,
+    /// A triangle wave.
+    Triangle,
+    /// A sum of harmonics which get brighter along the Y axis.
+    Additive,
+    /// A sine carrier frequency modulated by a sine at twice its frequency,
+    /// with the modulation index growing along the Y axis.
+    Fm,
+    /// Noise, different on every row.
+    Noise,
+}
+
+impl Initializer {
+    pub const ALL: [Initializer; 7] = [
+        Self::Sine,
+        Self::Saw,
+        Self::Square,
+        Self::Triangle,
+        Self::Additive,
+        Self::Fm,
+        Self::Noise,
+    ];
+
+    /// Pick an initializer from the first byte of `input`.
+    pub fn from_text(input: &[u8]) -> Self {
+        let [a, b] = NibbleStream::<2>::new(input).next_nibbles();
+        let byte = (a << 4) | b;
+        Self::ALL[byte as usize % Self::ALL.len()]
+    }
+
+    /// Get the value of the entry at the given `x` `y` coordinates of an `S`
+    /// by `S` table.
+    pub fn entry<const S: usize>(&self, x: usize, y: usize) -> u8 {
+        let x = x as f64 / S as f64;
+        let y = y as f64 / S as f64;
+
+        let value = match self {
+            Self::Sine => (TAU * x - TAU * y).sin(),
+            Self::Saw => 2.0 * x - 1.0,
+            Self::Square => {
+                let width = 0.05 + 0.9 * y;
+                if x < width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Self::Triangle => 1.0 - 4.0 * (x - 0.5).abs(),
+            Self::Additive => {
+                let partial = |k: usize| y.powi(k as i32 - 1) / k as f64;
+                let norm: f64 = (1..=ADDITIVE_HARMONICS).map(partial).sum();
+
+                (1..=ADDITIVE_HARMONICS)
+                    .map(|k| partial(k) * (TAU * k as f64 * x).sin())
+                    .sum::<f64>()
+                    / norm
+            }
+            Self::Fm => (TAU * x + FM_MAX_INDEX * y * (2.0 * TAU * x).sin()).sin(),
+            Self::Noise => {
+                let x = (x * S as f64) as usize;
+                let y = (y * S as f64) as usize;
+                let byte = RIJNDAEL_SBOX[(RIJNDAEL_SBOX[x % 256] as usize + y) % 256];
+                byte as f64 / 127.5 - 1.0
+            }
+        };
+
+        let value = 0.5 * value + 0.5;
+        let value = value * 255.0;
+
+        value as u8
+    }
+}
+
+impl Display for Initializer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Sine => "sine",
+            Self::Saw => "saw",
+            Self::Square => "square",
+            Self::Triangle => "triangle",
+            Self::Additive => "additive",
+            Self::Fm => "fm",
+            Self::Noise => "noise",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Initializer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|init| init.to_string() == s)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown initializer '{s}' (expected sine, saw, square, triangle, \
+                     additive, fm or noise)"
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Initializer;
+
+    #[test]
+    fn entries_span_the_range() {
+        const S: usize = 32;
+
+        for init in Initializer::ALL {
+            let entries: Vec<_> = (0..S)
+                .flat_map(|y| (0..S).map(move |x| init.entry::<S>(x, y)))
+                .collect();
+
+            let min = *entries.iter().min().unwrap();
+            let max = *entries.iter().max().unwrap();
+            assert!(min < 64 && max > 192, "{init}: {min}..{max}");
+
+            assert_eq!(Ok(init), init.to_string().parse().map_err(|_| ()));
+        }
+    }
+}
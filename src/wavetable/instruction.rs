@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use crate::bytes::NibbleStream;
+use crate::data::PRIMES;
+
+/// An instruction for the wavetable virtual machine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    /// Do nothing.
+    Noop,
+    /// Add the given `x` `y` pair to each cursor.
+    MultiplyCursor(usize, usize),
+    /// Introduce a cursor at the smallest x, y pair furthest from every other
+    /// cursor.
+    VoronoiCursor,
+    /// Multiply the data by the given value.
+    MultiplyData(usize),
+    /// Increase the slant in the neighborhood.
+    Slant,
+    /// Smooth out the neighborhood.
+    Smooth,
+    /// Apply the Rijndael S-Box to the data.
+    Substitution,
+    /// Move the cursor by interpreting the data as a pair of signed 4-bit
+    /// offsets.
+    SignedDataMove,
+    /// Move each cursor diagonal by its index, starting with the one at the
+    /// index specified by the given value (modulo the cursor length).
+    MoveDiagonal(usize),
+    /// Transpose the wavetable.
+    Transpose,
+    /// Remove the oldest cursor
+    RemoveOldest,
+    /// Apply a 3x3 gaussian blur.
+    Gaussian,
+    /// Rotate the row of each cursor left by the given amount.
+    RotateRow(usize),
+    /// Mirror the row of each cursor around the cursor, such that the half of
+    /// the row after the cursor becomes a mirror image of the half before it.
+    MirrorRow,
+    /// Reset the neighborhood to the values of the initial sine table.
+    ReseedSine,
+    /// Exclusive-or the row of each cursor with the row after it.
+    XorRows,
+}
+
+impl Instruction {
+    /// Whether this instruction may change the data of the wavetable, as
+    /// opposed to just its cursors.
+    pub fn modifies_data(&self) -> bool {
+        match self {
+            Self::MultiplyData(_)
+            | Self::Slant
+            | Self::Smooth
+            | Self::Substitution
+            | Self::Transpose
+            | Self::Gaussian
+            | Self::RotateRow(_)
+            | Self::MirrorRow
+            | Self::ReseedSine
+            | Self::XorRows => true,
+
+            Self::Noop
+            | Self::MultiplyCursor(..)
+            | Self::VoronoiCursor
+            | Self::SignedDataMove
+            | Self::MoveDiagonal(_)
+            | Self::RemoveOldest => false,
+        }
+    }
+
+    /// Decode an instruction from a source of nibbles. Calls `next` once for
+    /// the opcode, and once more for instructions with an operand.
+    pub fn decode(mut next: impl FnMut() -> u8) -> Self {
+        match next() {
+            0x0 => Instruction::Noop,
+
+            0x1 => {
+                let nibble = next();
+                let prime1 = PRIMES[nibble as usize];
+                let prime2 = PRIMES[0xf - nibble as usize];
+                Instruction::MultiplyCursor(prime1, prime2)
+            }
+
+            0x2 => Instruction::VoronoiCursor,
+            0x3 => Instruction::MultiplyData(PRIMES[next() as usize]),
+            0x4 => Instruction::Slant,
+            0x5 => Instruction::Smooth,
+            0x6 => Instruction::Substitution,
+            0x7 => Instruction::SignedDataMove,
+            0x8 => Instruction::MoveDiagonal(PRIMES[next() as usize]),
+            0x9 => Instruction::Transpose,
+            0xa => Instruction::RemoveOldest,
+            0xb => Instruction::Gaussian,
+            0xc => Instruction::RotateRow(PRIMES[next() as usize]),
+            0xd => Instruction::MirrorRow,
+            0xe => Instruction::ReseedSine,
+            0xf => Instruction::XorRows,
+
+            0x10..=u8::MAX => unreachable!("decoding expects nibbles"),
+        }
+    }
+}
+
+impl NibbleStream<1> {
+    pub fn next_instruction(&mut self) -> Instruction {
+        Instruction::decode(|| self.next_nibble())
+    }
+}
+
+impl Display for Instruction {
+    /// Format this instruction as its assembly mnemonic followed by its
+    /// operands.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Noop => write!(f, "nop"),
+            Self::MultiplyCursor(x, y) => write!(f, "mulcur {x} {y}"),
+            Self::VoronoiCursor => write!(f, "voronoi"),
+            Self::MultiplyData(by) => write!(f, "muldata {by}"),
+            Self::Slant => write!(f, "slant"),
+            Self::Smooth => write!(f, "smooth"),
+            Self::Substitution => write!(f, "sbox"),
+            Self::SignedDataMove => write!(f, "datamove"),
+            Self::MoveDiagonal(start) => write!(f, "diag {start}"),
+            Self::Transpose => write!(f, "transpose"),
+            Self::RemoveOldest => write!(f, "dropcur"),
+            Self::Gaussian => write!(f, "gauss"),
+            Self::RotateRow(by) => write!(f, "rotrow {by}"),
+            Self::MirrorRow => write!(f, "mirror"),
+            Self::ReseedSine => write!(f, "reseed"),
+            Self::XorRows => write!(f, "xorrow"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Instruction;
+    use crate::bytes::NibbleStream;
+
+    #[test]
+    fn decode_upper_opcodes() {
+        // the first nibble of a stream is used as is, while the second one of
+        // a single byte stream is always zero, picking the first prime
+        let mut nibbles = NibbleStream::<1>::new(&[0xc5]);
+        assert_eq!(Instruction::RotateRow(2), nibbles.next_instruction());
+
+        for (byte, expected) in [
+            (0xd0, Instruction::MirrorRow),
+            (0xe0, Instruction::ReseedSine),
+            (0xf0, Instruction::XorRows),
+        ] {
+            let mut nibbles = NibbleStream::<1>::new(&[byte]);
+            assert_eq!(expected, nibbles.next_instruction());
+        }
+    }
+}
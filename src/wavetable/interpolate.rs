@@ -0,0 +1,164 @@
+use std::f64::consts::{PI, TAU};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// The number of samples on either side of the interpolated point used by
+/// [`Interpolation::Sinc`].
+const SINC_RADIUS: isize = 8;
+
+/// The largest number of taps any interpolation mode uses.
+const MAX_TAPS: usize = 2 * SINC_RADIUS as usize;
+
+/// How to interpolate between the entries of the wavetable along one axis.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Interpolation {
+    /// Use the closest entry.
+    Nearest,
+    /// Linearly interpolate between the two closest entries.
+    #[default]
+    Linear,
+    /// Interpolate with a cubic Hermite (Catmull-Rom) spline through the four
+    /// closest entries.
+    Hermite,
+    /// Interpolate with a Blackman windowed sinc over the sixteen closest
+    /// entries.
+    Sinc,
+}
+
+/// A set of entry offsets and their weights, to be summed when interpolating.
+pub struct Taps {
+    offsets: [isize; MAX_TAPS],
+    weights: [f64; MAX_TAPS],
+    len: usize,
+}
+
+impl Interpolation {
+    /// Get the taps for interpolating at the fractional position `t` between
+    /// some entry (offset `0`) and the next one (offset `1`).
+    pub fn taps(&self, t: f64) -> Taps {
+        let mut taps = Taps {
+            offsets: [0; MAX_TAPS],
+            weights: [0.0; MAX_TAPS],
+            len: 0,
+        };
+
+        match self {
+            Self::Nearest => taps.push(if t < 0.5 { 0 } else { 1 }, 1.0),
+
+            Self::Linear => {
+                taps.push(0, 1.0 - t);
+                taps.push(1, t);
+            }
+
+            Self::Hermite => {
+                let t2 = t * t;
+                let t3 = t2 * t;
+
+                taps.push(-1, 0.5 * (-t3 + 2.0 * t2 - t));
+                taps.push(0, 0.5 * (3.0 * t3 - 5.0 * t2 + 2.0));
+                taps.push(1, 0.5 * (-3.0 * t3 + 4.0 * t2 + t));
+                taps.push(2, 0.5 * (t3 - t2));
+            }
+
+            Self::Sinc => {
+                for offset in 1 - SINC_RADIUS..=SINC_RADIUS {
+                    let distance = offset as f64 - t;
+                    taps.push(offset, sinc(distance) * blackman(distance));
+                }
+
+                // normalize, so a constant signal stays constant
+                let sum: f64 = taps.weights.iter().sum();
+                for weight in taps.weights.iter_mut() {
+                    *weight /= sum;
+                }
+            }
+        }
+
+        taps
+    }
+}
+
+impl Display for Interpolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Nearest => "nearest",
+            Self::Linear => "linear",
+            Self::Hermite => "hermite",
+            Self::Sinc => "sinc",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Interpolation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "linear" => Ok(Self::Linear),
+            "hermite" | "cubic" => Ok(Self::Hermite),
+            "sinc" => Ok(Self::Sinc),
+            _ => Err(anyhow!(
+                "unknown interpolation '{s}' (expected nearest, linear, hermite or sinc)"
+            )),
+        }
+    }
+}
+
+impl Taps {
+    fn push(&mut self, offset: isize, weight: f64) {
+        self.offsets[self.len] = offset;
+        self.weights[self.len] = weight;
+        self.len += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (isize, f64)> + '_ {
+        self.offsets[..self.len]
+            .iter()
+            .copied()
+            .zip(self.weights[..self.len].iter().copied())
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// A Blackman window spanning `[-SINC_RADIUS, SINC_RADIUS]`.
+fn blackman(x: f64) -> f64 {
+    let n = 0.5 + x / (2.0 * SINC_RADIUS as f64);
+    0.42 - 0.5 * (TAU * n).cos() + 0.08 * (2.0 * TAU * n).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interpolation;
+
+    #[test]
+    fn taps_pass_through_entries() {
+        let modes = [
+            Interpolation::Nearest,
+            Interpolation::Linear,
+            Interpolation::Hermite,
+            Interpolation::Sinc,
+        ];
+
+        for mode in modes {
+            for (offset, weight) in mode.taps(0.0).iter() {
+                let expected = if offset == 0 { 1.0 } else { 0.0 };
+                assert!((weight - expected).abs() < 1e-9, "{mode:?} {offset}");
+            }
+
+            let sum: f64 = mode.taps(0.3).iter().map(|(_, weight)| weight).sum();
+            assert!((sum - 1.0).abs() < 1e-9, "{mode:?}");
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::f64::consts::TAU;
+
+/// A set of progressively band-limited copies of a wavetable, one per octave.
+/// Level `0` is the table as is, while every following level contains at most
+/// half as many harmonics along the X axis as the one before it.
+pub struct Mipmaps<const S: usize> {
+    levels: Vec<Vec<f64>>,
+}
+
+/// A choice of levels to play back from. The played value is a crossfade
+/// between the `lower` and `upper` levels by `t`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Band {
+    lower: usize,
+    upper: usize,
+    t: f64,
+}
+
+impl Band {
+    /// Play back the table as is, without any band limiting.
+    pub const FULL: Band = Band {
+        lower: 0,
+        upper: 0,
+        t: 0.0,
+    };
+}
+
+impl<const S: usize> Mipmaps<S> {
+    pub fn new(data: &[[u8; S]; S]) -> Self {
+        let mut levels = vec![vec![0.0; S * S]];
+        let mut harmonics = S / 4;
+        while harmonics > 0 {
+            levels.push(vec![0.0; S * S]);
+            harmonics /= 2;
+        }
+
+        let mut this = Self { levels };
+        this.rebuild(data);
+        this
+    }
+
+    /// Recompute every level from the given data.
+    pub fn rebuild(&mut self, data: &[[u8; S]; S]) {
+        let twiddles: Vec<_> = (0..S)
+            .map(|i| {
+                let angle = TAU * i as f64 / S as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+
+        for (y, row) in data.iter().enumerate() {
+            let row: Vec<_> = row.iter().map(|value| *value as f64 / 255.0).collect();
+            self.levels[0][y * S..(y + 1) * S].copy_from_slice(&row);
+
+            // Compute the spectrum of this row, up to the highest harmonic any
+            // of the band limited levels need.
+            let spectrum: Vec<_> = (0..=S / 4)
+                .map(|k| {
+                    row.iter().enumerate().fold((0.0, 0.0), |(re, im), (x, v)| {
+                        let (cos, sin) = twiddles[(k * x) % S];
+                        (re + v * cos, im - v * sin)
+                    })
+                })
+                .collect();
+
+            for (level, values) in self.levels.iter_mut().enumerate().skip(1) {
+                let harmonics = Self::harmonics(level);
+                for (x, value) in values[y * S..(y + 1) * S].iter_mut().enumerate() {
+                    let partials: f64 = (1..=harmonics)
+                        .map(|k| {
+                            let (re, im) = spectrum[k];
+                            let (cos, sin) = twiddles[(k * x) % S];
+                            re * cos - im * sin
+                        })
+                        .sum();
+
+                    *value = (spectrum[0].0 + 2.0 * partials) / S as f64;
+                }
+            }
+        }
+    }
+
+    /// Choose the levels to play back from such that no harmonic above the
+    /// given `limit` remains. Both levels are at or below the limit, and the
+    /// crossfade moves towards the darker one as the limit drops, so sweeping
+    /// the limit never jumps between levels.
+    pub fn band(&self, limit: f64) -> Band {
+        let full = Self::harmonics(0) as f64;
+        if limit >= full {
+            return Band::FULL;
+        }
+
+        let last = self.levels.len() - 1;
+        let position = (full / limit.max(1.0)).log2();
+        let lower = (position.floor() as usize + 1).min(last);
+        let upper = (lower + 1).min(last);
+
+        Band {
+            lower,
+            upper,
+            t: position - position.floor(),
+        }
+    }
+
+    /// Get the value at the given integer coordinates within the given band.
+    pub fn get(&self, band: Band, x: usize, y: usize) -> f64 {
+        let a = self.levels[band.lower][y * S + x];
+        if band.t == 0.0 {
+            a
+        } else {
+            let b = self.levels[band.upper][y * S + x];
+            (1.0 - band.t) * a + band.t * b
+        }
+    }
+
+    /// The maximum number of harmonics present in the given level.
+    fn harmonics(level: usize) -> usize {
+        (S / 2) >> level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::{Band, Mipmaps};
+
+    #[test]
+    fn band_limiting_removes_high_harmonics() {
+        const S: usize = 64;
+
+        // a fundamental plus a loud 12th harmonic
+        let mut data = [[0; S]; S];
+        for row in data.iter_mut() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let x = TAU * x as f64 / S as f64;
+                let v = 0.5 + 0.25 * x.sin() + 0.25 * (12.0 * x).sin();
+                *value = (v * 255.0).round() as u8;
+            }
+        }
+
+        let mipmaps = Mipmaps::<S>::new(&data);
+
+        // level 2 has at most 8 harmonics, so only the fundamental survives
+        let band = Band {
+            lower: 2,
+            upper: 2,
+            t: 0.0,
+        };
+
+        for x in 0..S {
+            let expected = 0.5 + 0.25 * (TAU * x as f64 / S as f64).sin();
+            let actual = mipmaps.get(band, x, 0);
+            assert!((expected - actual).abs() < 0.01, "{expected} != {actual}");
+        }
+
+        assert_eq!(Band::FULL, mipmaps.band(S as f64));
+        assert_eq!(3, mipmaps.band(8.0).lower);
+    }
+
+    #[test]
+    fn band_removes_every_harmonic_above_the_limit() {
+        const S: usize = 64;
+
+        // a fundamental plus a 14th harmonic, just above a limit of 12
+        let mut data = [[0; S]; S];
+        for row in data.iter_mut() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let x = TAU * x as f64 / S as f64;
+                let v = 0.5 + 0.25 * x.sin() + 0.25 * (14.0 * x).sin();
+                *value = (v * 255.0).round() as u8;
+            }
+        }
+
+        let mipmaps = Mipmaps::<S>::new(&data);
+        let limit = 12.0;
+        let band = mipmaps.band(limit);
+        let row: Vec<_> = (0..S).map(|x| mipmaps.get(band, x, 0)).collect();
+
+        for k in (limit as usize + 1)..=S / 2 {
+            let (re, im) = row.iter().enumerate().fold((0.0, 0.0), |(re, im), (x, v)| {
+                let angle = TAU * (k * x) as f64 / S as f64;
+                (re + v * angle.cos(), im - v * angle.sin())
+            });
+            let magnitude = re.hypot(im) / S as f64;
+            assert!(magnitude < 1e-9, "harmonic {k} survives with {magnitude}");
+        }
+    }
+}
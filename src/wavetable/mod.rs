@@ -0,0 +1,653 @@
+pub mod asm;
+mod init;
+mod instruction;
+mod interpolate;
+mod mipmap;
+pub mod trace;
+
+pub use init::Initializer;
+pub use instruction::Instruction;
+pub use interpolate::Interpolation;
+pub use mipmap::Band;
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Context};
+use log::{debug, trace};
+
+use crate::data::RIJNDAEL_SBOX;
+use crate::math::MathExt;
+use crate::session::{self, Session};
+
+use self::mipmap::Mipmaps;
+
+pub const MAX_CURSORS: usize = 10;
+
+pub struct Wavetable<const S: usize> {
+    data: [[u8; S]; S],
+    cursors: VecDeque<(usize, usize)>,
+    mipmaps: Mipmaps<S>,
+    interpolation: (Interpolation, Interpolation),
+}
+
+/// The state of a wavetable at some point in time, which it can later be
+/// restored to.
+#[derive(Clone)]
+pub struct Snapshot<const S: usize> {
+    data: [[u8; S]; S],
+    cursors: VecDeque<(usize, usize)>,
+}
+
+impl<const S: usize> Snapshot<S> {
+    /// Read the data and cursors saved by [`Wavetable::save`].
+    pub fn load(session: &Session) -> anyhow::Result<Self> {
+        let rows: Vec<_> = session.get_all("table.row").collect();
+        if rows.len() != S {
+            bail!("expected {S} table rows, found {}", rows.len());
+        }
+
+        let mut data = [[0; S]; S];
+        for (row, hex) in data.iter_mut().zip(rows) {
+            if hex.len() != 2 * S || !hex.is_ascii() {
+                bail!("expected {S} hexadecimal values per table row");
+            }
+
+            for (value, i) in row.iter_mut().zip((0..hex.len()).step_by(2)) {
+                *value = u8::from_str_radix(&hex[i..i + 2], 16)
+                    .with_context(|| format!("invalid table value '{}'", &hex[i..i + 2]))?;
+            }
+        }
+
+        let mut cursors = VecDeque::new();
+        for value in session.get_all("table.cursor") {
+            let [x, y] = session::fields(value).context("in 'table.cursor'")?;
+            if x >= S || y >= S {
+                bail!("cursor ({x}, {y}) lies outside the table");
+            }
+
+            cursors.push_back((x, y));
+        }
+
+        if cursors.is_empty() || cursors.len() > MAX_CURSORS {
+            bail!("expected between 1 and {MAX_CURSORS} cursors");
+        }
+
+        Ok(Self { data, cursors })
+    }
+}
+
+impl<const S: usize> Wavetable<S> {
+    const SIZE: f64 = S as f64;
+
+    fn from_data(data: [[u8; S]; S]) -> Self {
+        Self {
+            mipmaps: Mipmaps::new(&data),
+            data,
+            cursors: VecDeque::from([(S / 2, S / 2)]),
+            interpolation: (Interpolation::Linear, Interpolation::Linear),
+        }
+    }
+
+    /// Create a wavetable filled by the given initializer.
+    pub fn new(init: Initializer) -> Self {
+        let mut data = [[0; S]; S];
+        for (y, data) in data.iter_mut().enumerate() {
+            for (x, data) in data.iter_mut().enumerate() {
+                *data = init.entry::<S>(x, y);
+            }
+        }
+
+        Self::from_data(data)
+    }
+
+    pub fn new_sine() -> Self {
+        Self::new(Initializer::Sine)
+    }
+
+    /// Create a wavetable from a signal in the range `[-1, 1]` made up of
+    /// single cycle frames of `frame_size` samples each. Every frame is
+    /// resampled to a row of `S` entries, and the rows in between frames are
+    /// crossfaded, so the first frame ends up in the first row and the last
+    /// frame in the last.
+    pub fn from_frames(samples: &[f32], frame_size: usize) -> Self {
+        let frames: Vec<_> = samples
+            .chunks_exact(frame_size.max(1))
+            .map(resample::<S>)
+            .collect();
+
+        if frames.is_empty() {
+            return Self::new_sine();
+        }
+
+        let mut data = [[0; S]; S];
+        for (y, row) in data.iter_mut().enumerate() {
+            let position = (y * (frames.len() - 1)) as f64 / (S - 1).max(1) as f64;
+            let lower = position as usize;
+            let upper = (lower + 1).min(frames.len() - 1);
+            let t = position - position.floor();
+
+            for (x, data) in row.iter_mut().enumerate() {
+                let value = (1.0 - t) * frames[lower][x] + t * frames[upper][x];
+                *data = ((value.clamp(-1.0, 1.0) + 1.0) / 2.0 * 255.0).round() as u8;
+            }
+        }
+
+        Self::from_data(data)
+    }
+
+    /// Get the wavetable slice at the given `y` coordinate. `y` is in the range
+    /// `[0, 1)`.
+    pub fn slice(&self, y: f64) -> Vec<u8> {
+        let y1_index = (y * Self::SIZE) as usize;
+        let y2_index = (y1_index + 1) % S;
+
+        let y1 = y1_index as f64 / Self::SIZE;
+        let y2 = (y1_index + 1) as f64 / Self::SIZE;
+        let t = (y - y1) / (y2 - y1);
+
+        let mut res = Vec::with_capacity(S);
+
+        for x in 0..S {
+            let a = self.data[y1_index][x] as f64 / 255.0;
+            let b = self.data[y2_index][x] as f64 / 255.0;
+            let value = (1.0 - t) * a + t * b;
+            res.push((value * 255.0) as u8);
+        }
+
+        res
+    }
+
+    /// Resample every row of the wavetable to `frame_size` samples along X,
+    /// and concatenate them into a single signal in the range `[-1, 1]`, as
+    /// the frames of a wavetable file.
+    pub fn frames(&self, frame_size: usize, interpolation: Interpolation) -> Vec<f32> {
+        // Every row is sampled exactly, so nearest interpolation along Y
+        // leaves it as is.
+        let modes = (interpolation, Interpolation::Nearest);
+
+        (0..S)
+            .flat_map(|y| (0..frame_size).map(move |i| (i, y)))
+            .map(|(i, y)| {
+                let x = i as f64 / frame_size as f64;
+                let y = y as f64 / Self::SIZE;
+                let value = self.sample_with(modes, Band::FULL, x, y);
+                (2.0 * value - 1.0) as f32
+            })
+            .collect()
+    }
+
+    /// Get the band to play back from such that a wave played at the given
+    /// `frequency` does not alias at the given `sample_rate`: no harmonic
+    /// above the Nyquist frequency is left in it.
+    pub fn band(&self, frequency: f64, sample_rate: f64) -> Band {
+        self.mipmaps.band(0.5 * sample_rate / frequency)
+    }
+
+    /// Set how to interpolate between entries along the X and Y axes.
+    pub fn set_interpolation(&mut self, x: Interpolation, y: Interpolation) {
+        self.interpolation = (x, y);
+    }
+
+    /// Sample the wavetable within the given band at the given `x` `y`
+    /// coordinates. `x` and `y` are in the range `[0, 1)`, and the resulting
+    /// value is interpolated according to [`Wavetable::set_interpolation`].
+    /// It lies roughly in the range `[0, 1]`, though the higher order
+    /// interpolation modes may overshoot slightly.
+    pub fn sample(&self, band: Band, x: f64, y: f64) -> f64 {
+        self.sample_with(self.interpolation, band, x, y)
+    }
+
+    /// Like [`Wavetable::sample`], but interpolating with the given modes
+    /// along X and Y instead.
+    fn sample_with(
+        &self,
+        (x_mode, y_mode): (Interpolation, Interpolation),
+        band: Band,
+        x: f64,
+        y: f64,
+    ) -> f64 {
+        // Compute the entry just before this coordinate, and how far past it
+        // the coordinate is.
+        let x = x * Self::SIZE;
+        let y = y * Self::SIZE;
+
+        let x1_index = x as isize;
+        let y1_index = y as isize;
+
+        let t_x = x - x.floor();
+        let t_y = y - y.floor();
+
+        let x_taps = x_mode.taps(t_x);
+        let y_taps = y_mode.taps(t_y);
+
+        // Interpolate!
+        let mut value = 0.0;
+        for (y_offset, y_weight) in y_taps.iter() {
+            let y_index = (y1_index + y_offset).rem_euclid(S as isize) as usize;
+
+            let row: f64 = x_taps
+                .iter()
+                .map(|(x_offset, x_weight)| {
+                    let x_index = (x1_index + x_offset).rem_euclid(S as isize) as usize;
+                    x_weight * self.mipmaps.get(band, x_index, y_index)
+                })
+                .sum();
+
+            value += y_weight * row;
+        }
+
+        value
+    }
+
+    pub fn snapshot(&self) -> Snapshot<S> {
+        Snapshot {
+            data: self.data,
+            cursors: self.cursors.clone(),
+        }
+    }
+
+    /// Restore the data and cursors of this table to those of the given
+    /// snapshot.
+    pub fn restore(&mut self, snapshot: &Snapshot<S>) {
+        self.data = snapshot.data;
+        self.cursors = snapshot.cursors.clone();
+        self.mipmaps.rebuild(&self.data);
+    }
+
+    /// Save the data and cursors of this table, with one hexadecimal line per
+    /// row.
+    pub fn save(&self, session: &mut Session) {
+        for row in &self.data {
+            let hex: String = row.iter().map(|value| format!("{value:02x}")).collect();
+            session.set("table.row", hex);
+        }
+
+        for (x, y) in self.cursors() {
+            session.set("table.cursor", format!("{x} {y}"));
+        }
+    }
+
+    /// Restore the data and cursors of this table from a session, leaving it
+    /// as it was if the session is invalid.
+    pub fn load(&mut self, session: &Session) -> anyhow::Result<()> {
+        let snapshot = Snapshot::load(session)?;
+        self.restore(&snapshot);
+        Ok(())
+    }
+
+    /// Get the current cursors, oldest first.
+    pub fn cursors(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cursors.iter().copied()
+    }
+
+    pub fn increment(&mut self) {
+        for (x, y) in self.cursors.iter_mut() {
+            *x = if *x == 0 { S - 1 } else { *x - 1 };
+            *y = if *y == 0 { S - 1 } else { *y - 1 };
+        }
+    }
+
+    pub fn execute(&mut self, inst: Instruction) {
+        trace!("{:?}", self.cursors);
+
+        match inst {
+            Instruction::Noop => {
+                debug!("no-op");
+            }
+            Instruction::MultiplyCursor(by_x, by_y) => {
+                debug!("multiply cursor {by_x} {by_y}");
+                for (x, y) in self.cursors.iter_mut() {
+                    *x = (*x * by_x) % S;
+                    *y = (*y * by_y) % S;
+                }
+            }
+            Instruction::VoronoiCursor => {
+                debug!("voronoi cursor");
+                let mut furthest = ((S / 2, S / 2), 0);
+
+                for y in (0..S).rev() {
+                    for x in (0..S).rev() {
+                        let dist = self
+                            .cursors
+                            .iter()
+                            .copied()
+                            .map(|(x2, y2)| {
+                                ((x as isize - x2 as isize).abs() + (y as isize - y2 as isize))
+                                    as usize
+                            })
+                            .min()
+                            .unwrap_or(0);
+                        if dist <= furthest.1 {
+                            furthest = ((x, y), dist);
+                        }
+                    }
+                }
+
+                self.cursors.push_back(furthest.0);
+                if self.cursors.len() >= MAX_CURSORS {
+                    self.cursors.pop_front();
+                }
+            }
+            Instruction::MultiplyData(by) => {
+                debug!("multiply data {by}");
+                for (x, y) in self.cursors.iter().copied() {
+                    self.data[y][x] = self.data[y][x].wrapping_mul(by as u8);
+                }
+            }
+            Instruction::Slant => {
+                debug!("slant");
+                for (x, y) in self.cursors.iter().copied() {
+                    slant(&mut self.data, x, y);
+                }
+            }
+            Instruction::Smooth => {
+                debug!("smooth");
+                for (x, y) in self.cursors.iter().copied() {
+                    smooth(&mut self.data, x, y);
+                }
+            }
+            Instruction::Substitution => {
+                debug!("substitution");
+                for (x, y) in self.cursors.iter().copied() {
+                    self.data[y][x] = RIJNDAEL_SBOX[self.data[y][x] as usize];
+                }
+            }
+            Instruction::SignedDataMove => {
+                debug!("signed data move");
+                for (x, y) in self.cursors.iter_mut() {
+                    let data = self.data[*y][*x];
+                    let xoff = data & 0xf0;
+                    let yoff = data << 4;
+
+                    let xoff = (xoff & 0x70) as isize + if xoff & 0x80 != 0 { -0x80 } else { 0 };
+                    let yoff = (yoff & 0x70) as isize + if yoff & 0x80 != 0 { -0x80 } else { 0 };
+
+                    *x = (*x as isize + xoff).rem_euclid(S as isize) as usize;
+                    *y = (*y as isize + yoff).rem_euclid(S as isize) as usize;
+                }
+            }
+            Instruction::MoveDiagonal(start) => {
+                debug!("move diagonal {start}");
+                if !self.cursors.is_empty() {
+                    let start = start % self.cursors.len();
+                    let indices = (start..self.cursors.len()).chain(0..start);
+
+                    for (off, i) in (1..).zip(indices) {
+                        let (x, y) = self.cursors[i];
+                        self.cursors[i] = ((x + off) % S, (y + off) % S);
+                    }
+                }
+            }
+            Instruction::Transpose => {
+                debug!("transpose");
+                for y in 0..S - 1 {
+                    for x in y + 1..S {
+                        let tmp = self.data[y][x];
+                        self.data[y][x] = self.data[x][y];
+                        self.data[x][y] = tmp;
+                    }
+                }
+            }
+            Instruction::RemoveOldest => {
+                debug!("remove oldest");
+                if self.cursors.len() > 1 {
+                    self.cursors.pop_front();
+                }
+            }
+            Instruction::Gaussian => {
+                debug!("gaussian blur");
+                for (x, y) in self.cursors.iter().copied() {
+                    gaussian(&mut self.data, x, y);
+                }
+            }
+            Instruction::RotateRow(by) => {
+                debug!("rotate row {by}");
+                for (_, y) in self.cursors.iter().copied() {
+                    self.data[y].rotate_left(by % S);
+                }
+            }
+            Instruction::MirrorRow => {
+                debug!("mirror row");
+                for (x, y) in self.cursors.iter().copied() {
+                    for i in 1..S / 2 {
+                        self.data[y][(x + i) % S] = self.data[y][(x + S - i) % S];
+                    }
+                }
+            }
+            Instruction::ReseedSine => {
+                debug!("reseed sine");
+                for (x, y) in self.cursors.iter().copied() {
+                    for y in [(y + S - 1) % S, y, (y + 1) % S] {
+                        for x in [(x + S - 1) % S, x, (x + 1) % S] {
+                            self.data[y][x] = sine::<S>(x, y);
+                        }
+                    }
+                }
+            }
+            Instruction::XorRows => {
+                debug!("xor rows");
+                for (_, y) in self.cursors.iter().copied() {
+                    let below = self.data[(y + 1) % S];
+                    for (data, below) in self.data[y].iter_mut().zip(below) {
+                        *data ^= below;
+                    }
+                }
+            }
+        }
+
+        if inst.modifies_data() {
+            self.mipmaps.rebuild(&self.data);
+        }
+    }
+}
+
+/// Get the value of the initial sine table at this `x` `y` coordinate pair.
+fn sine<const S: usize>(x: usize, y: usize) -> u8 {
+    Initializer::Sine.entry::<S>(x, y)
+}
+
+/// Make the immediate neighborhood of this `x` `y` coordinate pair into a plane
+/// with the largest slope.
+fn slant<const S: usize>(data: &mut [[u8; S]; S], x: usize, y: usize) {
+    let x1 = if x == 0 { S - 1 } else { x - 1 };
+    let x2 = if x == S - 1 { 0 } else { x + 1 };
+
+    let y1 = if y == 0 { S - 1 } else { y - 1 };
+    let y2 = if y == S - 1 { 0 } else { y + 1 };
+
+    let thi = data[y][x] as i16;
+
+    let lef = data[y][x1] as i16;
+    let rig = data[y][x2] as i16;
+
+    let bot = data[y1][x] as i16;
+    let top = data[y2][x] as i16;
+
+    let (dx1, dx2) = (thi - lef, rig - thi);
+    let (dy1, dy2) = (thi - bot, top - thi);
+
+    let dx = dx1.max_abs(dx2);
+    let dy = dy1.max_abs(dy2);
+
+    data[y1][x1] = (data[y1][x1] as i16 - dx - dy).rem_euclid(u8::MAX as i16) as u8;
+    data[y1][x] = (data[y1][x] as i16 - dy).rem_euclid(u8::MAX as i16) as u8;
+    data[y1][x2] = (data[y1][x2] as i16 + dx - dy).rem_euclid(u8::MAX as i16) as u8;
+    data[y][x1] = (data[y1][x1] as i16 - dx).rem_euclid(u8::MAX as i16) as u8;
+    data[y][x2] = (data[y1][x2] as i16 + dx).rem_euclid(u8::MAX as i16) as u8;
+    data[y2][x1] = (data[y2][x1] as i16 - dx + dy).rem_euclid(u8::MAX as i16) as u8;
+    data[y2][x] = (data[y2][x] as i16 + dy).rem_euclid(u8::MAX as i16) as u8;
+    data[y2][x2] = (data[y2][x2] as i16 + dx + dy).rem_euclid(u8::MAX as i16) as u8;
+}
+
+/// Resample a single cycle `frame` to `S` entries, averaging over each entry
+/// when shrinking it and linearly interpolating when growing it.
+fn resample<const S: usize>(frame: &[f32]) -> [f64; S] {
+    let mut row = [0.0; S];
+    let n = frame.len();
+
+    for (x, value) in row.iter_mut().enumerate() {
+        *value = if n >= S {
+            let span = &frame[x * n / S..(x + 1) * n / S];
+            span.iter().map(|v| *v as f64).sum::<f64>() / span.len() as f64
+        } else {
+            let position = (x * n) as f64 / S as f64;
+            let i = position as usize;
+            let t = position - position.floor();
+            (1.0 - t) * frame[i] as f64 + t * frame[(i + 1) % n] as f64
+        };
+    }
+
+    row
+}
+
+/// Smooth out the neighborhood of this `x` `y` coordinate pair.
+fn smooth<const S: usize>(data: &mut [[u8; S]; S], x: usize, y: usize) {
+    let x1 = if x == 0 { S - 1 } else { x - 1 };
+    let x2 = if x == S - 1 { 0 } else { x + 1 };
+
+    let y1 = if y == 0 { S - 1 } else { y - 1 };
+    let y2 = if y == S - 1 { 0 } else { y + 1 };
+
+    smooth_one(data, x1, y1);
+    smooth_one(data, x, y1);
+    smooth_one(data, x2, y1);
+    smooth_one(data, x1, y);
+    smooth_one(data, x, y);
+    smooth_one(data, x2, y);
+    smooth_one(data, x1, y2);
+    smooth_one(data, x, y2);
+    smooth_one(data, x2, y2);
+}
+
+/// Smooth out this `x` `y` coordinate pair.
+#[inline(always)]
+fn smooth_one<const S: usize>(data: &mut [[u8; S]; S], x: usize, y: usize) {
+    let x1 = if x == 0 { S - 1 } else { x - 1 };
+    let x2 = if x == S - 1 { 0 } else { x + 1 };
+
+    let y1 = if y == 0 { S - 1 } else { y - 1 };
+    let y2 = if y == S - 1 { 0 } else { y + 1 };
+
+    let thi = data[y][x] as i16;
+
+    let lef = data[y][x1] as i16;
+    let rig = data[y][x2] as i16;
+
+    let bot = data[y1][x] as i16;
+    let top = data[y2][x] as i16;
+
+    let dx2 = rig - 2 * thi + lef;
+    let dy2 = top - 2 * thi + bot;
+
+    let diff = dx2 + dy2;
+    data[y][x] = (data[y][x] as i16)
+        .saturating_add(diff)
+        .rem_euclid(u8::MAX as i16) as u8;
+}
+
+fn gaussian<const S: usize>(data: &mut [[u8; S]; S], x: usize, y: usize) {
+    let x1 = if x == 0 { S - 1 } else { x - 1 };
+    let x2 = if x == S - 1 { 0 } else { x + 1 };
+
+    let y1 = if y == 0 { S - 1 } else { y - 1 };
+    let y2 = if y == S - 1 { 0 } else { y + 1 };
+
+    let value = 0.0625 * data[y1][x1] as f64
+        + 0.1250 * data[y1][x] as f64
+        + 0.0625 * data[y1][x2] as f64
+        + 0.1250 * data[y][x1] as f64
+        + 0.2500 * data[y][x] as f64
+        + 0.1250 * data[y][x2] as f64
+        + 0.0625 * data[y2][x1] as f64
+        + 0.1250 * data[y2][x] as f64
+        + 0.0625 * data[y2][x2] as f64;
+
+    data[y][x] = value as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::{sine, Instruction, Wavetable};
+
+    const S: usize = 8;
+
+    fn table(cursor: (usize, usize)) -> Wavetable<S> {
+        let mut data = [[0; S]; S];
+        for (y, row) in data.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = (S * y + x) as u8;
+            }
+        }
+
+        let mut table = Wavetable::from_data(data);
+        table.cursors = VecDeque::from([cursor]);
+        table
+    }
+
+    #[test]
+    fn rotate_row() {
+        let mut table = table((0, 2));
+        table.execute(Instruction::RotateRow(3));
+
+        assert_eq!([19, 20, 21, 22, 23, 16, 17, 18], table.data[2]);
+        assert_eq!([8, 9, 10, 11, 12, 13, 14, 15], table.data[1]);
+    }
+
+    #[test]
+    fn mirror_row() {
+        let mut table = table((2, 1));
+        table.execute(Instruction::MirrorRow);
+
+        assert_eq!([8, 9, 10, 9, 8, 15, 14, 15], table.data[1]);
+    }
+
+    #[test]
+    fn reseed_sine() {
+        let mut table = table((0, 0));
+        table.execute(Instruction::ReseedSine);
+
+        for y in [S - 1, 0, 1] {
+            for x in [S - 1, 0, 1] {
+                assert_eq!(sine::<S>(x, y), table.data[y][x]);
+            }
+        }
+
+        assert_eq!(2, table.data[0][2]);
+    }
+
+    #[test]
+    fn xor_rows() {
+        let mut table = table((5, S - 1));
+        table.execute(Instruction::XorRows);
+
+        let expected: Vec<_> = (0..S).map(|x| (S * (S - 1) + x) as u8 ^ x as u8).collect();
+        assert_eq!(expected, table.data[S - 1]);
+    }
+
+    #[test]
+    fn from_frames() {
+        // two frames: a rising ramp and silence
+        let ramp = (0..16).map(|i| i as f32 / 8.0 - 1.0);
+        let samples: Vec<_> = ramp.chain([0.0; 16]).collect();
+        let table = Wavetable::<S>::from_frames(&samples, 16);
+
+        // each entry averages two samples of the ramp
+        assert_eq!([8, 40, 72, 104, 135, 167, 199, 231], table.data[0]);
+        assert_eq!([128; S], table.data[S - 1]);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut table = table((2, 1));
+        let snapshot = table.snapshot();
+
+        table.execute(Instruction::XorRows);
+        table.increment();
+        table.restore(&snapshot);
+
+        assert_eq!(VecDeque::from([(2, 1)]), table.cursors);
+        assert_eq!([8, 9, 10, 11, 12, 13, 14, 15], table.data[1]);
+    }
+}
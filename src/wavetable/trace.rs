@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use super::{Instruction, Wavetable};
+
+/// A single executed instruction of a program.
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The nibbles the instruction was decoded from.
+    pub nibbles: Vec<u8>,
+    pub instruction: Instruction,
+
+    /// The cursors right after executing the instruction, before they are
+    /// moved on to the next step.
+    pub cursors: Vec<(usize, usize)>,
+}
+
+/// Run the wavetable virtual machine on the nibbles produced by `next` for
+/// the given number of steps, the same way a performer does, and record every
+/// step.
+pub fn trace<const S: usize>(
+    mut table: Wavetable<S>,
+    mut next: impl FnMut() -> u8,
+    steps: usize,
+) -> Vec<Step> {
+    (0..steps)
+        .map(|_| {
+            let mut nibbles = vec![];
+            let instruction = Instruction::decode(|| {
+                let nibble = next();
+                nibbles.push(nibble);
+                nibble
+            });
+
+            table.execute(instruction);
+            let cursors = table.cursors().collect();
+            table.increment();
+
+            Step {
+                nibbles,
+                instruction,
+                cursors,
+            }
+        })
+        .collect()
+}
+
+/// Format the steps as a human readable listing, with one line per step.
+pub fn listing(steps: &[Step]) -> String {
+    let mut res = String::new();
+    writeln!(
+        res,
+        "{:>5}  {:<4} {:<14} cursors",
+        "step", "hex", "instruction"
+    )
+    .unwrap();
+
+    for (i, step) in steps.iter().enumerate() {
+        let nibbles = step.nibbles.iter().map(|n| format!("{n:x}")).join(" ");
+        let cursors = step
+            .cursors
+            .iter()
+            .map(|(x, y)| format!("({x}, {y})"))
+            .join(" ");
+
+        let instruction = step.instruction.to_string();
+        writeln!(res, "{i:>5}  {nibbles:<4} {instruction:<14} {cursors}").unwrap();
+    }
+
+    res
+}
+
+/// Format the steps as a JSON array, with one object per step.
+pub fn json(steps: &[Step]) -> String {
+    let steps = steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let nibbles = step.nibbles.iter().join(", ");
+            let cursors = step
+                .cursors
+                .iter()
+                .map(|(x, y)| format!("[{x}, {y}]"))
+                .join(", ");
+
+            let instruction = json_string(&step.instruction.to_string());
+            format!(
+                "  {{\"step\": {i}, \"nibbles\": [{nibbles}], \"instruction\": {instruction}, \"cursors\": [{cursors}]}}"
+            )
+        })
+        .join(",\n");
+
+    format!("[\n{steps}\n]\n")
+}
+
+/// Quote `text` as a JSON string, escaping it as needed.
+fn json_string(text: &str) -> String {
+    let mut res = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => write!(res, "\\u{:04x}", c as u32).unwrap(),
+            c => res.push(c),
+        }
+    }
+
+    res.push('"');
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json, json_string, listing, trace};
+    use crate::wavetable::{Instruction, Wavetable};
+
+    #[test]
+    fn trace_known_program() {
+        let mut nibbles = [0x0, 0x1, 0x1, 0x8, 0x0].into_iter();
+        let steps = trace(Wavetable::<8>::new_sine(), || nibbles.next().unwrap(), 3);
+
+        // the single cursor starts in the middle, and moves back diagonally by
+        // one between steps, so `mulcur` takes it from (3, 3) to (1, 5)
+        let decoded: Vec<_> = steps
+            .iter()
+            .map(|step| (step.nibbles.clone(), step.instruction, step.cursors.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                (vec![0x0], Instruction::Noop, vec![(4, 4)]),
+                (
+                    vec![0x1, 0x1],
+                    Instruction::MultiplyCursor(3, 47),
+                    vec![(1, 5)]
+                ),
+                (vec![0x8, 0x0], Instruction::MoveDiagonal(2), vec![(1, 5)]),
+            ],
+            decoded
+        );
+
+        assert_eq!(
+            r#"[
+  {"step": 0, "nibbles": [0], "instruction": "nop", "cursors": [[4, 4]]},
+  {"step": 1, "nibbles": [1, 1], "instruction": "mulcur 3 47", "cursors": [[1, 5]]},
+  {"step": 2, "nibbles": [8, 0], "instruction": "diag 2", "cursors": [[1, 5]]}
+]
+"#,
+            json(&steps)
+        );
+
+        assert!(listing(&steps)
+            .lines()
+            .nth(2)
+            .unwrap()
+            .contains("mulcur 3 47"));
+        assert_eq!(r#""say \"hi\"\\\n""#, json_string("say \"hi\"\\\n"));
+    }
+}